@@ -1,184 +1,32 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::env;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
 
-use serde::Deserialize;
+use bitcoin_fee_model_codegen::{RawModelData, SIZES};
 
-#[derive(Deserialize, Debug)]
-pub struct ModelData {
-    pub norm: FieldsDescribe,
-    pub weights: Weights,
-    pub fields: Vec<String>,
-    pub alpha: f32,
-}
-
-impl ModelData {
-    fn into_src(self, model_name: &str) -> (HashSet<usize>, String) {
-        let fields = self
-            .fields
-            .iter()
-            .fold(String::new(), |acc, f| acc + "\"" + f + "\".to_string(), ");
-
-        let i_size = self.fields.len();
-        let l0_size = self.weights.l0_bias.len();
-        let l1_size = self.weights.l1_bias.len();
-        let o_size = self.weights.l2_bias.len();
-
-        if l0_size != l1_size {
-            panic!(
-                "Layer 0 and Layer 1 must have the same number of neurons. Found: {}, {}",
-                l0_size, l1_size
-            );
-        }
-        if o_size != 1 {
-            panic!("Layer 2 should only have one output. Found: {}", o_size);
-        }
-
-        let req_sizes = vec![i_size, l0_size, o_size].into_iter().collect();
-        let src = format!(
-            r#"
+fn get_model_src(
+    model: RawModelData,
+    model_name: &str,
+) -> Result<(HashSet<usize>, String), String> {
+    let i_size = model.fields.len();
+    let n_size = model.weights.l0_bias.len();
+    let o_size = model.weights.l2_bias.len();
+    let (req_sizes, expr) = model.into_expr("crate")?;
+    let src = format!(
+        r#"
         pub fn get_model_{name}() -> ModelData<Size{i_size}, Size{n_size}, Size{o_size}> {{
-            ModelData {{
-                norm: {norm},
-                weights: {weights},
-                fields: vec![{fields}],
-                alpha: {alpha},
-            }}
-        }}
-        "#,
-            i_size = i_size,
-            n_size = l0_size,
-            o_size = o_size,
-            name = model_name,
-            norm = self.norm.as_src(),
-            weights = self.weights.into_src(),
-            fields = fields,
-            alpha = self.alpha
-        );
-
-        (req_sizes, src)
-    }
-}
-
-#[derive(Deserialize, Debug)]
-pub struct FieldsDescribe {
-    mean: HashMap<String, f32>,
-    std: HashMap<String, f32>,
-}
-
-impl FieldsDescribe {
-    fn as_src(&self) -> String {
-        let mean = self
-            .mean
-            .iter()
-            .map(|(k, v)| format!("(\"{}\".to_string(), {})", k, v))
-            .fold(String::new(), |acc, f| acc + &f + ", ");
-        let std = self
-            .std
-            .iter()
-            .map(|(k, v)| format!("(\"{}\".to_string(), {})", k, v))
-            .fold(String::new(), |acc, f| acc + &f + ", ");
-
-        format!(
-            r#"
-        FieldsDescribe {{
-            mean: vec![{mean}].into_iter().collect(),
-            std: vec![{std}].into_iter().collect(),
+            {expr}
         }}
         "#,
-            mean = mean,
-            std = std
-        )
-    }
-}
-
-#[derive(Deserialize, Debug)]
-pub struct Weights {
-    #[serde(rename = "dense/bias:0")]
-    pub l0_bias: Vec<f32>,
-    #[serde(rename = "dense/kernel:0")]
-    pub l0_kernel: Vec<Vec<f32>>,
-
-    #[serde(rename = "dense_1/bias:0")]
-    pub l1_bias: Vec<f32>,
-    #[serde(rename = "dense_1/kernel:0")]
-    pub l1_kernel: Vec<Vec<f32>>,
-
-    #[serde(rename = "dense_2/bias:0")]
-    pub l2_bias: Vec<f32>,
-    #[serde(rename = "dense_2/kernel:0")]
-    pub l2_kernel: Vec<Vec<f32>>,
-}
-
-fn compress_buffer(v: Vec<f32>) -> String {
-    let v_bytes = unsafe { std::slice::from_raw_parts(v.as_ptr() as *const u8, v.len() * 4) };
-    let s = v_bytes
-        .iter()
-        .map(|c| std::ascii::escape_default(*c))
-        .flatten()
-        .map(char::from)
-        .collect::<String>();
-
-    format!("b\"{}\"", s)
-}
-
-fn decompress_buffer(data: String) -> String {
-    format!(
-        r#"{{
-        let data: Vec<u8> = {data}.to_vec();
-        let v_floats = unsafe {{ Vec::from_raw_parts(data.as_ptr() as *mut f32, data.len() / 4, data.len() / 4) }};
-
-        std::mem::forget(data);
-
-        v_floats
-    }}"#,
-        data = data
-    )
-}
-
-impl Weights {
-    fn into_src(self) -> String {
-        fn serialize_vec(field_name: &str, v: Vec<f32>) -> String {
-            format!(
-                r#"
-                {field}: Matrix::from_buffer({data}.into_boxed_slice())
-            "#,
-                field = field_name,
-                data = decompress_buffer(compress_buffer(v))
-            )
-        }
-        fn serialize_matrix(field_name: &str, v: Vec<Vec<f32>>) -> String {
-            format!(
-                r#"
-                {field}: Matrix::from_buffer({data}.into_boxed_slice())
-            "#,
-                field = field_name,
-                data =
-                    decompress_buffer(compress_buffer(v.into_iter().flatten().collect::<Vec<_>>()))
-            )
-        }
-
-        format!(
-            r#"
-            Weights {{
-                {l0_bias},
-                {l0_kernel},
-                {l1_bias},
-                {l1_kernel},
-                {l2_bias},
-                {l2_kernel},
-            }}
-            "#,
-            l0_bias = serialize_vec("l0_bias", self.l0_bias),
-            l0_kernel = serialize_matrix("l0_kernel", self.l0_kernel),
-            l1_bias = serialize_vec("l1_bias", self.l1_bias),
-            l1_kernel = serialize_matrix("l1_kernel", self.l1_kernel),
-            l2_bias = serialize_vec("l2_bias", self.l2_bias),
-            l2_kernel = serialize_matrix("l2_kernel", self.l2_kernel),
-        )
-    }
+        name = model_name,
+        i_size = i_size,
+        n_size = n_size,
+        o_size = o_size,
+        expr = expr
+    );
+    Ok((req_sizes, src))
 }
 
 fn emit_sizes_src(sizes: &HashSet<usize>) -> String {
@@ -205,45 +53,190 @@ fn add_model(
     path: &str,
     model_name: &str,
 ) -> Result<(HashSet<usize>, String), Box<dyn std::error::Error>> {
-    let model: ModelData = serde_cbor::from_reader(File::open(path)?)?;
+    let model: RawModelData = serde_cbor::from_reader(File::open(path)?)?;
     println!("cargo:rerun-if-changed={}", path);
 
-    Ok(model.into_src(model_name))
+    get_model_src(model, model_name).map_err(|e| format!("{}: {}", path, e).into())
+}
+
+/// the C type of the rust type `t` used in `src/ffi.rs`
+fn c_type(t: &str) -> String {
+    let t = t.trim();
+    if let Some(pointee) = t.strip_prefix("*const ") {
+        return format!("const {} *", c_type(pointee));
+    }
+    if let Some(pointee) = t.strip_prefix("*mut ") {
+        return format!("{} *", c_type(pointee));
+    }
+    match t {
+        "u8" => "uint8_t",
+        "u16" => "uint16_t",
+        "u32" => "uint32_t",
+        "u64" => "uint64_t",
+        "i32" => "int32_t",
+        "usize" => "size_t",
+        "f32" => "float",
+        "f64" => "double",
+        "" => "void",
+        other => other,
+    }
+    .to_string()
+}
+
+/// `FeeModelError` to `FEE_MODEL_ERROR`
+fn screaming_snake(camel: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in camel.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            result.push('_');
+        }
+        result.push(c.to_ascii_uppercase());
+    }
+    result
+}
+
+/// the C header of the items declared in `src/ffi.rs`, the opaque types, constants, `repr(C)`
+/// enums and `extern "C"` functions, with their doc comments
+// `split_once` and `rsplit_once` are not available on the minimum supported rust version
+#[allow(clippy::manual_split_once, clippy::needless_splitn)]
+fn ffi_header(src: &str) -> String {
+    let mut header = String::from(
+        "/* Generated by build.rs from src/ffi.rs, do not edit */\n\n\
+         #ifndef BITCOIN_FEE_MODEL_H\n\
+         #define BITCOIN_FEE_MODEL_H\n\n\
+         #include <stddef.h>\n\
+         #include <stdint.h>\n",
+    );
+    let mut docs = String::new();
+    let mut lines = src.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line == "#[cfg(test)]" {
+            break;
+        }
+        if let Some(doc) = line.strip_prefix("///") {
+            docs += &format!("//{}\n", doc);
+            continue;
+        }
+        if line.starts_with("#[") {
+            continue;
+        }
+        let item = if let Some(rest) = line.strip_prefix("pub type ") {
+            let name = rest.split('=').next().unwrap().trim();
+            format!("typedef struct {name} {name};\n", name = name)
+        } else if let Some(rest) = line.strip_prefix("pub const ") {
+            let name = rest.split(':').next().unwrap().trim();
+            let value = rest.split('=').nth(1).unwrap().trim_end_matches(';').trim();
+            format!("#define {} {}\n", name, value)
+        } else if let Some(rest) = line.strip_prefix("pub enum ") {
+            let name = rest.trim_end_matches('{').trim();
+            let mut item = "typedef enum {\n".to_string();
+            for variant in lines.by_ref().map(str::trim).take_while(|l| *l != "}") {
+                item += &format!(
+                    "    {}_{}\n",
+                    screaming_snake(name),
+                    screaming_snake(variant)
+                );
+            }
+            item + &format!("}} {};\n", name)
+        } else if line.starts_with("pub extern \"C\" fn ")
+            || line.starts_with("pub unsafe extern \"C\" fn ")
+        {
+            // signatures formatted over several lines are joined until the body
+            let mut signature = line.to_string();
+            while !signature.ends_with('{') {
+                let next = lines
+                    .next()
+                    .expect("unterminated extern \"C\" fn in src/ffi.rs");
+                signature += " ";
+                signature += next.trim();
+            }
+            // generics and where clauses have no C equivalent and their `,` would split arguments
+            if signature.contains('<') || signature.contains(" where ") {
+                panic!("generic extern \"C\" fn in src/ffi.rs: {}", signature);
+            }
+            let name = signature
+                .splitn(2, " fn ")
+                .nth(1)
+                .unwrap()
+                .splitn(2, '(')
+                .next()
+                .unwrap()
+                .trim();
+            let after_name = signature.splitn(2, '(').nth(1).unwrap();
+            let mut parts = after_name.rsplitn(2, ')');
+            let ret = parts.next().unwrap();
+            let args = parts.next().unwrap();
+            let ret = ret.trim_end_matches('{').trim().trim_start_matches("->");
+            let args: Vec<String> = args
+                .split(',')
+                .filter(|a| !a.trim().is_empty())
+                .map(|a| {
+                    let mut parts = a.splitn(2, ':');
+                    let arg = parts.next().unwrap();
+                    let t = c_type(parts.next().unwrap());
+                    let sep = if t.ends_with('*') { "" } else { " " };
+                    format!("{}{}{}", t, sep, arg.trim())
+                })
+                .collect();
+            let ret = c_type(ret);
+            let sep = if ret.ends_with('*') { "" } else { " " };
+            let args = if args.is_empty() {
+                "void".to_string()
+            } else {
+                args.join(", ")
+            };
+            format!("{}{}{}({});\n", ret, sep, name, args)
+        } else {
+            docs.clear();
+            continue;
+        };
+        header += "\n";
+        header += &docs;
+        header += &item;
+        docs.clear();
+    }
+    header + "\n#endif /* BITCOIN_FEE_MODEL_H */\n"
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    const CUSTOM_FEE_ERR: &str = "Custom models must be specified with a comma separated list of `<name>:<path>`, with no space in between. Trailing commas are not supported";
+    const CUSTOM_FEE_ERR: &str = "Custom models must be specified with a comma separated list of `<name>:<path>`, with no space in between";
 
     let default_models = vec![
         ("test_model", "./models/test_model.cbor"),
+        ("test_quantile_model", "./models/test_quantile_model.cbor"),
         ("low", "./models/20211027-180849/model.cbor"), // for 1,2 blocks
         ("high", "./models/20211027-180925/model.cbor"),
     ];
-    let extra_models = option_env!("CUSTOM_FEE_MODELS")
-        .map(|s| s.split(','))
-        .map(|ps| {
-            ps.map(|p| p.split(':')).map(|mut p| {
-                (
-                    p.next().expect(CUSTOM_FEE_ERR),
-                    p.next().expect(CUSTOM_FEE_ERR),
-                )
-            })
-        })
-        .map(|s| s.collect::<Vec<_>>());
+    // deprecated in favor of the `include_fee_model!` macro of the `macros` feature
+    println!("cargo:rerun-if-env-changed=CUSTOM_FEE_MODELS");
+    let custom_models = match env::var_os("CUSTOM_FEE_MODELS") {
+        Some(models) => {
+            println!("cargo:warning=CUSTOM_FEE_MODELS is deprecated, use `include_fee_model!` of the `macros` feature");
+            models.into_string().map_err(|_| CUSTOM_FEE_ERR)?
+        }
+        None => String::new(),
+    };
+    let mut extra_models = vec![];
+    for model in custom_models.split(',').filter(|m| !m.is_empty()) {
+        // only the first `:` separates the name, the path may contain others like `C:\models`
+        let mut parts = model.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(path)) if !name.is_empty() && !path.is_empty() => {
+                extra_models.push((name, path))
+            }
+            _ => return Err(CUSTOM_FEE_ERR.into()),
+        }
+    }
 
     let out_dir = env::var_os("OUT_DIR").unwrap();
     let models_path = Path::new(&out_dir).join("models.rs");
     let sizes_path = Path::new(&out_dir).join("sizes.rs");
 
-    let mut sizes = vec![1, 2, 4, 8, 16, 20, 32, 64, 128, 256, 512]
-        .into_iter()
-        .collect::<HashSet<_>>();
+    let mut sizes = SIZES.iter().cloned().collect::<HashSet<_>>();
     let mut models_file = File::create(models_path)?;
 
-    for (name, path) in default_models
-        .into_iter()
-        .chain(extra_models.unwrap_or_default())
-    {
+    for (name, path) in default_models.into_iter().chain(extra_models) {
         let (req_sizes, content) = add_model(path, name)?;
 
         models_file.write_all(content.as_bytes())?;
@@ -252,5 +245,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     fs::write(&sizes_path, emit_sizes_src(&sizes)).unwrap();
 
+    if env::var_os("CARGO_FEATURE_FFI").is_some() {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        let header = ffi_header(&fs::read_to_string("src/ffi.rs")?);
+        fs::write(Path::new(&out_dir).join("bitcoin_fee_model.h"), header)?;
+    }
+
     Ok(())
 }
@@ -0,0 +1,9 @@
+//! Companion crate of `bitcoin-fee-model`: the layout of the `model.cbor` files and the source
+//! code embedding them, shared by its build script, its `include_fee_model!` macro and the
+//! loading of models at runtime
+
+mod model_src;
+mod raw_model;
+
+pub use model_src::SIZES;
+pub use raw_model::{RawFieldsDescribe, RawModelData, RawWeights};
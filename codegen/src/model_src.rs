@@ -0,0 +1,127 @@
+//! Source code of the embedded models
+
+use std::collections::HashSet;
+
+use crate::raw_model::{RawFieldsDescribe, RawModelData, RawWeights};
+
+/// the sizes for which a `SizeN` type is always available
+pub const SIZES: [usize; 12] = [1, 2, 3, 4, 8, 16, 20, 32, 64, 128, 256, 512];
+
+impl RawModelData {
+    /// the sizes required by the model and an expression building it, `krate` is the path of
+    /// the `bitcoin_fee_model` crate where the expression is used.
+    /// Returns an error if the shapes of the weights are not consistent
+    pub fn into_expr(self, krate: &str) -> Result<(HashSet<usize>, String), String> {
+        self.validate()?;
+        let fields = self
+            .fields
+            .iter()
+            .fold(String::new(), |acc, f| acc + &format!("{:?}, ", f));
+
+        let i_size = self.fields.len();
+        let l0_size = self.weights.l0_bias.len();
+        let o_size = self.weights.l2_bias.len();
+
+        let quantiles = self
+            .quantiles
+            .iter()
+            .fold(String::new(), |acc, q| acc + &format!("{:?}f32, ", q));
+
+        let req_sizes = vec![i_size, l0_size, o_size].into_iter().collect();
+        let src = format!(
+            r#"
+            {krate}::ModelData::<{krate}::Size{i_size}, {krate}::Size{n_size}, {krate}::Size{o_size}> {{
+                norm: {norm},
+                weights: {weights},
+                fields: {krate}::__private::strings(&[{fields}]),
+                alpha: {alpha:?},
+                quantiles: <[f32]>::to_vec(&[{quantiles}]),
+                utc_offset: {utc_offset},
+            }}
+            "#,
+            krate = krate,
+            i_size = i_size,
+            n_size = l0_size,
+            o_size = o_size,
+            norm = self.norm.as_src(krate),
+            weights = self.weights.into_src(krate),
+            fields = fields,
+            alpha = self.alpha,
+            quantiles = quantiles,
+            utc_offset = self.utc_offset
+        );
+
+        Ok((req_sizes, src))
+    }
+}
+
+impl RawFieldsDescribe {
+    fn as_src(&self, krate: &str) -> String {
+        let mean = self
+            .mean
+            .iter()
+            .map(|(k, v)| format!("({:?}, {:?}f32)", k, v))
+            .fold(String::new(), |acc, f| acc + &f + ", ");
+        let std = self
+            .std
+            .iter()
+            .map(|(k, v)| format!("({:?}, {:?}f32)", k, v))
+            .fold(String::new(), |acc, f| acc + &f + ", ");
+
+        format!(
+            r#"
+            {krate}::__private::fields_describe(&[{mean}], &[{std}])
+            "#,
+            krate = krate,
+            mean = mean,
+            std = std
+        )
+    }
+}
+
+/// the weights as a byte string literal of little-endian `f32`, decoded by
+/// `matrix_from_le_bytes` so that the generated code is the same on every host
+fn le_bytes_literal(v: Vec<f32>) -> String {
+    let s = v
+        .iter()
+        // arrays are not iterable by value on the minimum supported rust version
+        .flat_map(|f| f.to_le_bytes().to_vec())
+        .flat_map(std::ascii::escape_default)
+        .map(char::from)
+        .collect::<String>();
+
+    format!("b\"{}\"", s)
+}
+
+impl RawWeights {
+    fn into_src(self, krate: &str) -> String {
+        let field = |name: &str, v: Vec<f32>| {
+            format!(
+                "{name}: {krate}::__private::matrix_from_le_bytes({data})",
+                name = name,
+                krate = krate,
+                data = le_bytes_literal(v)
+            )
+        };
+
+        format!(
+            r#"
+            {krate}::__private::Weights {{
+                {l0_bias},
+                {l0_kernel},
+                {l1_bias},
+                {l1_kernel},
+                {l2_bias},
+                {l2_kernel},
+            }}
+            "#,
+            krate = krate,
+            l0_bias = field("l0_bias", self.l0_bias),
+            l0_kernel = field("l0_kernel", self.l0_kernel.concat()),
+            l1_bias = field("l1_bias", self.l1_bias),
+            l1_kernel = field("l1_kernel", self.l1_kernel.concat()),
+            l2_bias = field("l2_bias", self.l2_bias),
+            l2_kernel = field("l2_kernel", self.l2_kernel.concat()),
+        )
+    }
+}
@@ -0,0 +1,103 @@
+//! Layout of the `model.cbor` files
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RawModelData {
+    pub norm: RawFieldsDescribe,
+    pub weights: RawWeights,
+    pub fields: Vec<String>,
+    pub alpha: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quantiles: Vec<f32>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub utc_offset: i32,
+}
+
+fn is_zero(v: &i32) -> bool {
+    *v == 0
+}
+
+impl RawModelData {
+    /// check the shapes of the weights are consistent with the fields and the quantiles, the
+    /// same checks are done when the model is embedded at build time and when it's loaded at
+    /// runtime
+    pub fn validate(&self) -> Result<(), String> {
+        let i_size = self.fields.len();
+        let l0_size = self.weights.l0_bias.len();
+        let l1_size = self.weights.l1_bias.len();
+        let o_size = self.weights.l2_bias.len();
+
+        if l0_size != l1_size {
+            return Err(format!(
+                "Layer 0 and Layer 1 must have the same number of neurons. Found: {}, {}",
+                l0_size, l1_size
+            ));
+        }
+        if self.quantiles.windows(2).any(|w| w[0] >= w[1])
+            || self.quantiles.iter().any(|q| !(0.0..=1.0).contains(q))
+        {
+            return Err(format!(
+                "Quantiles must be strictly increasing. Found: {:?}",
+                self.quantiles
+            ));
+        }
+        if (o_size != 1 || !self.quantiles.is_empty()) && o_size != self.quantiles.len() {
+            return Err(format!(
+                "Layer 2 must have one output per quantile, or a single one without quantiles. Found: {}, {}",
+                o_size,
+                self.quantiles.len()
+            ));
+        }
+        check_kernel("Layer 0", &self.weights.l0_kernel, i_size, l0_size)?;
+        check_kernel("Layer 1", &self.weights.l1_kernel, l0_size, l1_size)?;
+        check_kernel("Layer 2", &self.weights.l2_kernel, l1_size, o_size)?;
+        Ok(())
+    }
+}
+
+/// check `kernel` has `inputs` rows of `outputs` weights
+fn check_kernel(
+    layer: &str,
+    kernel: &[Vec<f32>],
+    inputs: usize,
+    outputs: usize,
+) -> Result<(), String> {
+    if kernel.len() != inputs || kernel.iter().any(|row| row.len() != outputs) {
+        return Err(format!(
+            "{} kernel must be {}x{}. Found: {}x{:?}",
+            layer,
+            inputs,
+            outputs,
+            kernel.len(),
+            kernel.iter().map(Vec::len).collect::<BTreeSet<_>>()
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RawFieldsDescribe {
+    pub mean: BTreeMap<String, f32>,
+    pub std: BTreeMap<String, f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RawWeights {
+    #[serde(rename = "dense/bias:0")]
+    pub l0_bias: Vec<f32>,
+    #[serde(rename = "dense/kernel:0")]
+    pub l0_kernel: Vec<Vec<f32>>,
+
+    #[serde(rename = "dense_1/bias:0")]
+    pub l1_bias: Vec<f32>,
+    #[serde(rename = "dense_1/kernel:0")]
+    pub l1_kernel: Vec<Vec<f32>>,
+
+    #[serde(rename = "dense_2/bias:0")]
+    pub l2_bias: Vec<f32>,
+    #[serde(rename = "dense_2/kernel:0")]
+    pub l2_kernel: Vec<Vec<f32>>,
+}
@@ -0,0 +1,87 @@
+use bitcoin_fee_model::backtest::{Backtest, TargetStats};
+use bitcoin_fee_model::bitcoin::{consensus::deserialize, Block};
+use bitcoin_fee_model::estimator::PercentileEstimator;
+use bitcoin_fee_model::{get_model_high, get_model_low, Error, FeeModel, ModelData, SizeMarker};
+use bitcoin_fee_model::{Size128, Size64};
+use std::collections::BTreeMap;
+use std::fs;
+
+type Report = BTreeMap<u16, TargetStats>;
+
+/// Usage: `cargo run --example backtest --features use-bitcoin,cbor -- <dir> [<low.cbor> <high.cbor>]`
+/// where `<dir>` contains consecutive raw blocks, one per file, ordered by file name.
+/// The bundled models are compared with the percentile heuristic and, if given, with candidate
+/// `model.cbor` files having 64 or 128 neurons per layer
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 2 && args.len() != 4 {
+        return Err("expected <dir> [<low.cbor> <high.cbor>]".into());
+    }
+    let mut paths: Vec<_> = fs::read_dir(&args[1])?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+    let blocks = paths
+        .iter()
+        .map(|p| Ok(deserialize::<Block>(&fs::read(p)?)?))
+        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+
+    let backtest = Backtest::default();
+    let model = FeeModel::new(get_model_low(), get_model_high());
+    let mut reports = vec![("bundled", backtest.run(&model, &blocks)?)];
+    if args.len() == 4 {
+        let low = fs::read(&args[2])?;
+        let high = fs::read(&args[3])?;
+        // only a shape mismatch of the candidate means it has 128 neurons, other errors are reported
+        let candidate = match load::<Size64>(&low, &high) {
+            Ok(candidate) => backtest.run(&candidate, &blocks)?,
+            Err(Error::InvalidModel(_)) => backtest.run(&load::<Size128>(&low, &high)?, &blocks)?,
+            Err(e) => return Err(e.into()),
+        };
+        reports.push(("candidate", candidate));
+    }
+    let percentile = PercentileEstimator::default();
+    reports.push(("percentile", backtest.run(&percentile, &blocks)?));
+
+    print(&backtest.targets, &reports);
+    Ok(())
+}
+
+fn load<N: SizeMarker>(low: &[u8], high: &[u8]) -> Result<FeeModel<N>, Error> {
+    Ok(FeeModel::new(
+        ModelData::from_cbor(low)?,
+        ModelData::from_cbor(high)?,
+    ))
+}
+
+/// one row per target, with the stats of every report side by side
+fn print(targets: &[u16], reports: &[(&str, Report)]) {
+    let names: String = reports
+        .iter()
+        .map(|(name, _)| format!(" {:>36}", name))
+        .collect();
+    println!("{:>6}{}", "", names);
+    println!(
+        "{:>6}{}",
+        "target",
+        "    count  hit rate  overpay  errors".repeat(reports.len())
+    );
+    let or_dash = |value: Option<f64>, precision: usize| match value {
+        Some(value) => format!("{:.*}", precision, value),
+        None => "-".to_string(),
+    };
+    for target in targets {
+        let mut row = format!("{:>6}", target);
+        for (_, report) in reports {
+            let stats = &report[target];
+            row += &format!(
+                " {:>8} {:>9} {:>8} {:>7}",
+                stats.count,
+                or_dash(stats.hit_rate(), 3),
+                or_dash(stats.mean_overpayment(), 2),
+                stats.errors
+            );
+        }
+        println!("{}", row);
+    }
+}
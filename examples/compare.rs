@@ -0,0 +1,70 @@
+use bitcoin_fee_model::compare::{compare, Comparison};
+use bitcoin_fee_model::{get_model_high, get_model_low, Error, FeeModel, ModelData, SizeMarker};
+use bitcoin_fee_model::{Size128, Size64};
+use std::collections::HashMap;
+use std::fs;
+
+type Dataset = Vec<HashMap<String, f32>>;
+
+/// Usage: `cargo run --example compare --features cbor -- <low.cbor> <high.cbor> <dataset.csv>`
+/// compare the bundled models with candidate `model.cbor` files having 64 or 128 neurons per
+/// layer.
+/// The dataset is a csv with an header containing the names of the features
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 4 {
+        return Err("expected <low.cbor> <high.cbor> <dataset.csv>".into());
+    }
+    let low = fs::read(&args[1])?;
+    let high = fs::read(&args[2])?;
+
+    let csv = fs::read_to_string(&args[3])?;
+    let mut lines = csv.lines();
+    let header: Vec<&str> = lines.next().ok_or("empty dataset")?.split(',').collect();
+    let mut dataset = vec![];
+    for line in lines.filter(|l| !l.is_empty()) {
+        let mut features = HashMap::new();
+        for (name, value) in header.iter().zip(line.split(',')) {
+            features.insert(name.trim().to_string(), value.trim().parse::<f32>()?);
+        }
+        dataset.push(features);
+    }
+
+    // only a shape mismatch of the candidate means it has 128 neurons, other errors are reported
+    match load::<Size64>(&low, &high) {
+        Ok(candidate) => report(&candidate, &dataset)?,
+        Err(Error::InvalidModel(_)) => report(&load::<Size128>(&low, &high)?, &dataset)?,
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(())
+}
+
+fn load<N: SizeMarker>(low: &[u8], high: &[u8]) -> Result<FeeModel<N>, Error> {
+    Ok(FeeModel::new(
+        ModelData::from_cbor(low)?,
+        ModelData::from_cbor(high)?,
+    ))
+}
+
+fn report<N: SizeMarker>(candidate: &FeeModel<N>, dataset: &Dataset) -> Result<(), Error> {
+    let current = FeeModel::new(get_model_low(), get_model_high());
+
+    let (all, per_target) = compare(&current, candidate, dataset)?;
+    println!("target    count  median cur  median cand   mean diff  mean |diff|  correlation");
+    for (target, c) in per_target.iter() {
+        print(&target.to_string(), c);
+    }
+    if let Some(all) = all {
+        print("all", &all);
+    }
+
+    Ok(())
+}
+
+fn print(target: &str, c: &Comparison) {
+    println!(
+        "{:>6} {:>8} {:>11.2} {:>12.2} {:>11.2} {:>12.2} {:>12.3}",
+        target, c.count, c.a.median, c.b.median, c.mean_diff, c.mean_abs_diff, c.correlation
+    );
+}
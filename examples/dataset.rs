@@ -0,0 +1,51 @@
+use bitcoin_fee_model::bitcoin::Txid;
+use bitcoin_fee_model::block_files::read_block_file;
+use bitcoin_fee_model::dataset::{build, write_csv, MempoolObservation};
+use std::fs;
+use std::str::FromStr;
+
+/// Usage: `cargo run --example dataset --features use-bitcoin -- <dir> <observations.csv>`
+/// where `<dir>` contains consecutive raw blocks, one per file, ordered by file name, and
+/// `<observations.csv>` has `txid,timestamp,fee_rate` rows of the transactions seen in the
+/// mempool. The dataset is written to stdout
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        return Err("expected <dir> <observations.csv>".into());
+    }
+    let mut paths: Vec<_> = fs::read_dir(&args[1])?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+    let blocks = paths
+        .iter()
+        .map(read_block_file)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut observations = vec![];
+    for line in fs::read_to_string(&args[2])?.lines() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let txid = match fields.first().map(|t| Txid::from_str(t)) {
+            Some(Ok(txid)) => txid,
+            // header or empty line
+            _ => continue,
+        };
+        if fields.len() != 3 {
+            return Err(format!("invalid line: {}", line).into());
+        }
+        observations.push(MempoolObservation {
+            txid,
+            timestamp: fields[1].parse()?,
+            fee_rate: fields[2].parse()?,
+        });
+    }
+
+    let rows = build(&blocks, &observations)?;
+    eprintln!(
+        "{} rows from {} observations",
+        rows.len(),
+        observations.len()
+    );
+    write_csv(&rows, std::io::stdout().lock())?;
+    Ok(())
+}
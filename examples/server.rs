@@ -0,0 +1,39 @@
+use bitcoin_fee_model::block_files::read_last_blocks;
+use bitcoin_fee_model::block_source::MemoryBlockSource;
+use bitcoin_fee_model::server::FeeServer;
+use bitcoin_fee_model::{get_model_high, get_model_low, FeeModel};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Usage: `cargo run --example server --features server -- <blocks dir> <addr>`
+/// serve the estimates computed from the last blocks in a Bitcoin Core blocks directory, which
+/// is read again every minute to follow the new blocks
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let dir = args.next().ok_or("missing blocks directory")?;
+    let addr = args.next().unwrap_or_else(|| "127.0.0.1:3000".to_string());
+
+    let server = Arc::new(FeeServer::new(FeeModel::new(
+        get_model_low(),
+        get_model_high(),
+    )));
+    let source = MemoryBlockSource::new(read_last_blocks(&dir, 10)?);
+    server.update(&source)?;
+
+    let updater = server.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(60));
+        match read_last_blocks(&dir, 10) {
+            Ok(blocks) => {
+                if let Err(e) = updater.update(&MemoryBlockSource::new(blocks)) {
+                    eprintln!("update failed: {}", e);
+                }
+            }
+            Err(e) => eprintln!("reading {} failed: {}", dir, e),
+        }
+    });
+
+    println!("listening on {}", addr);
+    server.run(&addr)?;
+    Ok(())
+}
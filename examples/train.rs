@@ -0,0 +1,39 @@
+use bitcoin_fee_model::train::{read_csv, train, TrainConfig};
+use std::fs;
+use std::path::Path;
+
+/// Usage: `cargo run --release --example train --features train -- <dataset.csv> <low|high> <out dir> [epochs]`
+/// train the model for transactions confirming in 1 or 2 blocks (`low`) or from 3 to 1008 blocks
+/// (`high`) and write `model.cbor` and `test_vector.cbor` in `<out dir>`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 4 {
+        return Err("expected <dataset.csv> <low|high> <out dir> [epochs]".into());
+    }
+    let rows = read_csv(&fs::read_to_string(&args[1])?)?;
+    let low = match args[2].as_str() {
+        "low" => true,
+        "high" => false,
+        _ => return Err("expected low or high".into()),
+    };
+    let rows: Vec<_> = rows
+        .into_iter()
+        .filter(|r| (r.get("confirms_in").cloned().unwrap_or(0.0) <= 2.0) == low)
+        .collect();
+
+    let mut config = TrainConfig::default();
+    if let Some(epochs) = args.get(4) {
+        config.epochs = epochs.parse()?;
+    }
+    println!("training on {} rows", rows.len());
+    let model = train(&rows, &config)?;
+    for (epoch, loss) in model.losses.iter().enumerate() {
+        println!("epoch {:>4} loss {:.4}", epoch + 1, loss);
+    }
+
+    let dir = Path::new(&args[3]);
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("model.cbor"), model.to_cbor())?;
+    fs::write(dir.join("test_vector.cbor"), model.test_vector_cbor())?;
+    Ok(())
+}
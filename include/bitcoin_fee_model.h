@@ -0,0 +1,58 @@
+/* Generated by build.rs from src/ffi.rs, do not edit */
+
+#ifndef BITCOIN_FEE_MODEL_H
+#define BITCOIN_FEE_MODEL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Opaque handle to the bundled models
+typedef struct FeeModel FeeModel;
+
+// number of fee buckets written by `fee_model_process_blocks`
+#define FEE_BUCKETS_LEN 16
+
+// Error codes, mirroring the variants of the Rust `Error`
+typedef enum {
+    FEE_MODEL_ERROR_OK = 0,
+    FEE_MODEL_ERROR_MISSING_MEAN_DATA = 1,
+    FEE_MODEL_ERROR_MISSING_STD_DATA = 2,
+    FEE_MODEL_ERROR_UNCONNECTED_BLOCKS = 3,
+    FEE_MODEL_ERROR_LAST_TS_MISSING = 4,
+    FEE_MODEL_ERROR_MISSING_QUANTILES = 5,
+    FEE_MODEL_ERROR_QUANTILE_OUT_OF_RANGE = 6,
+    FEE_MODEL_ERROR_MISSING_FEE_RATES = 7,
+    FEE_MODEL_ERROR_INVALID_MODEL = 8,
+    FEE_MODEL_ERROR_BLOCK_SOURCE = 9,
+    FEE_MODEL_ERROR_INVALID_DATASET = 10,
+    FEE_MODEL_ERROR_INVALID_INPUT = 11,
+    FEE_MODEL_ERROR_MISSING_TIMESTAMP = 12,
+    FEE_MODEL_ERROR_NULL_POINTER = 13,
+} FeeModelError;
+
+// Create the bundled models, to be released with `fee_model_free`
+FeeModel *fee_model_new(void);
+
+// Release the models created with `fee_model_new`, `model` may be null
+//
+// # Safety
+// `model` must be returned by `fee_model_new` and not already released
+void fee_model_free(FeeModel *model);
+
+// Write to `out` the fee rate in sat/vbytes to confirm within `block_target` given the
+// `fee_buckets` written by `fee_model_process_blocks`
+//
+// # Safety
+// `model` must be a live handle, `fee_buckets` must point to `fee_buckets_len` values and `out`
+// to a writable float
+FeeModelError fee_model_estimate_with_buckets(const FeeModel *model, uint16_t block_target, uint32_t timestamp, const uint64_t *fee_buckets, size_t fee_buckets_len, uint32_t last_block_ts, float *out);
+
+// Compute the fee buckets and the timestamp of the last block from the 10 last blocks, serialized
+// one after the other from the oldest in `blocks`
+//
+// # Safety
+// `blocks` must point to `blocks_len` bytes, `fee_buckets` to `fee_buckets_len` writable values
+// and `last_block_ts` to a writable integer
+FeeModelError fee_model_process_blocks(const uint8_t *blocks, size_t blocks_len, uint64_t *fee_buckets, size_t fee_buckets_len, uint32_t *last_block_ts);
+
+#endif /* BITCOIN_FEE_MODEL_H */
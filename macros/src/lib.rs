@@ -0,0 +1,151 @@
+//! Companion crate of `bitcoin-fee-model`, use it through the `macros` feature of that crate
+
+use std::fs;
+use std::path::PathBuf;
+
+use proc_macro::{TokenStream, TokenTree};
+
+use bitcoin_fee_model_codegen::{RawModelData, SIZES};
+
+/// Embed the `model.cbor` file at the given path, relative to the directory containing the
+/// `Cargo.toml` of the calling crate, as an expression of type `ModelData<I, N, O>` where `I` is
+/// the number of fields, `N` of neurons and `O` of outputs.
+/// Shapes of the weights are checked at compile time and the crate is rebuilt when the file changes.
+///
+/// ```ignore
+/// use bitcoin_fee_model::{include_fee_model, ModelData, Size1, Size20, Size64};
+///
+/// fn get_model_custom() -> ModelData<Size20, Size64, Size1> {
+///     include_fee_model!("models/custom/model.cbor")
+/// }
+/// ```
+#[proc_macro]
+pub fn include_fee_model(input: TokenStream) -> TokenStream {
+    let src = match expand(input) {
+        Ok(src) => src,
+        Err(e) => format!("compile_error!({:?})", e),
+    };
+    src.parse().expect("generated code is valid")
+}
+
+fn expand(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    let path = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => string_literal(&literal.to_string())
+            .ok_or("include_fee_model! expects a string literal")?,
+        _ => return Err("include_fee_model! expects a single string literal".to_string()),
+    };
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").map_err(|e| e.to_string())?;
+    let path = PathBuf::from(manifest_dir).join(path);
+    let path = path.to_str().ok_or("path is not valid utf8")?;
+
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    model_expr(&bytes).map(|expr| {
+        format!(
+            "{{ const _: &[u8] = include_bytes!({:?}); {} }}",
+            path, expr
+        )
+    })
+}
+
+/// the expression building the model in the cbor `bytes`
+fn model_expr(bytes: &[u8]) -> Result<String, String> {
+    let model: RawModelData = serde_cbor::from_slice(bytes).map_err(|e| e.to_string())?;
+    let (req_sizes, expr) = model.into_expr("::bitcoin_fee_model")?;
+    let mut unsupported: Vec<_> = req_sizes
+        .into_iter()
+        .filter(|s| !SIZES.contains(s))
+        .collect();
+    unsupported.sort_unstable();
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "Sizes {:?} are not supported, supported sizes are {:?}",
+            unsupported, SIZES
+        ));
+    }
+    Ok(expr)
+}
+
+/// the content of a string literal without escapes, as `"a/b"` or `r#"a\b"#`
+fn string_literal(literal: &str) -> Option<String> {
+    let content = match literal.strip_prefix('r') {
+        Some(raw) => {
+            let hashes = raw.len() - raw.trim_start_matches('#').len();
+            raw.get(hashes..raw.len() - hashes)?
+                .strip_prefix('"')?
+                .strip_suffix('"')?
+        }
+        None => {
+            let content = literal.strip_prefix('"')?.strip_suffix('"')?;
+            if content.contains('\\') {
+                return None;
+            }
+            content
+        }
+    };
+    Some(content.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{model_expr, string_literal};
+    use bitcoin_fee_model_codegen::RawModelData;
+
+    const TEST_MODEL: &[u8] = include_bytes!("../../models/test_model.cbor");
+
+    #[test]
+    fn test_string_literal() {
+        assert_eq!(string_literal("\"a/b.cbor\""), Some("a/b.cbor".to_string()));
+        assert_eq!(string_literal("r\"a\\b\""), Some("a\\b".to_string()));
+        assert_eq!(string_literal("r#\"a\"b\"#"), Some("a\"b".to_string()));
+        assert_eq!(string_literal("\"a\\nb\""), None);
+        assert_eq!(string_literal("1"), None);
+        assert_eq!(string_literal("r#\"a\""), None);
+    }
+
+    #[test]
+    fn test_model_expr() {
+        let expr = model_expr(TEST_MODEL).unwrap();
+        assert!(expr.contains("::bitcoin_fee_model::ModelData::<::bitcoin_fee_model::Size20, ::bitcoin_fee_model::Size4, ::bitcoin_fee_model::Size1>"));
+    }
+
+    #[test]
+    fn test_model_expr_errors() {
+        let with = |f: &dyn Fn(&mut RawModelData)| {
+            let mut model: RawModelData = serde_cbor::from_slice(TEST_MODEL).unwrap();
+            f(&mut model);
+            model_expr(&serde_cbor::to_vec(&model).unwrap()).unwrap_err()
+        };
+
+        assert!(model_expr(b"not cbor").is_err());
+        assert_eq!(
+            with(&|m| {
+                m.fields.pop();
+            }),
+            "Layer 0 kernel must be 19x4. Found: 20x{4}"
+        );
+        assert_eq!(
+            with(&|m| m.weights.l1_kernel[2].push(0.0)),
+            "Layer 1 kernel must be 4x4. Found: 4x{4, 5}"
+        );
+        assert_eq!(
+            with(&|m| m.weights.l1_bias.push(0.0)),
+            "Layer 0 and Layer 1 must have the same number of neurons. Found: 4, 5"
+        );
+        assert_eq!(
+            with(&|m| m.quantiles = vec![0.25, 0.5, 0.75]),
+            "Layer 2 must have one output per quantile, or a single one without quantiles. Found: 1, 3"
+        );
+        assert_eq!(
+            with(&|m| m.quantiles = vec![0.5, 0.4]),
+            "Quantiles must be strictly increasing. Found: [0.5, 0.4]"
+        );
+        assert_eq!(
+            with(&|m| {
+                m.fields.pop();
+                m.weights.l0_kernel.pop();
+            }),
+            "Sizes [19] are not supported, supported sizes are [1, 2, 3, 4, 8, 16, 20, 32, 64, 128, 256, 512]"
+        );
+    }
+}
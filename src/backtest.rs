@@ -0,0 +1,202 @@
+use crate::estimator::{percentile, BlocksData, FeeEstimator};
+use crate::process_blocks::{process_blocks_by_block, Transactions};
+use bitcoin::{Block, Transaction, Txid};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+
+/// Backtest configuration
+#[derive(Debug, Clone)]
+pub struct Backtest {
+    /// block targets to evaluate
+    pub targets: Vec<u16>,
+    /// the percentile of the fee rates of a block considered the minimum fee rate to be included,
+    /// using a low percentile instead of the minimum ignores CPFP parents and miners' transactions
+    pub inclusion_percentile: f64,
+}
+
+/// Results for a single block target
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TargetStats {
+    /// number of estimates whose outcome is known
+    pub count: usize,
+    /// number of estimates that would have confirmed within the target
+    pub hits: usize,
+    /// number of estimates that returned an error
+    pub errors: usize,
+    /// sum of the difference in sat/vbytes between the estimate and the lowest fee rate that would
+    /// have confirmed within the target, only for hits
+    pub overpayment: f64,
+}
+
+impl TargetStats {
+    /// the ratio of hits to estimates, None without estimates
+    pub fn hit_rate(&self) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.hits as f64 / self.count as f64)
+    }
+
+    /// mean overpayment in sat/vbytes of the hits, None without hits
+    pub fn mean_overpayment(&self) -> Option<f64> {
+        if self.hits == 0 {
+            return None;
+        }
+        Some(self.overpayment / self.hits as f64)
+    }
+}
+
+impl Default for Backtest {
+    fn default() -> Self {
+        Backtest {
+            targets: vec![1, 2, 3, 6, 12, 24],
+            inclusion_percentile: 5.0,
+        }
+    }
+}
+
+impl Backtest {
+    /// replay `blocks`, ordered and connected, estimating with `estimator` at the time of every
+    /// block having 9 predecessors and enough successors to know the outcome. Windows of 10
+    /// blocks that can't be processed are counted in [`TargetStats::errors`]
+    pub fn run<E: FeeEstimator>(
+        &self,
+        estimator: &E,
+        blocks: &[Block],
+    ) -> Result<BTreeMap<u16, TargetStats>, crate::Error> {
+        let txs: HashMap<Txid, Transaction> = blocks
+            .iter()
+            .flat_map(|b| b.txdata.iter())
+            .map(|tx| (tx.txid(), tx.clone()))
+            .collect();
+        let inclusion: Vec<Option<f64>> = Transactions::from_txs(txs, 0)
+            .fee_rates_by_block(blocks)
+            .iter()
+            .map(|rates| percentile(rates, self.inclusion_percentile))
+            .collect();
+
+        let mut result: BTreeMap<u16, TargetStats> = self
+            .targets
+            .iter()
+            .map(|t| (*t, TargetStats::default()))
+            .collect();
+        for i in 9..blocks.len() {
+            let window: &[Block; 10] = blocks[i - 9..=i].try_into().unwrap();
+            // a window that can't be processed counts as an error of every estimate made on it
+            let processed = process_blocks_by_block(window);
+            let timestamp = Some(blocks[i].header.time);
+
+            for (target, stats) in result.iter_mut() {
+                let end = i + *target as usize;
+                if end >= blocks.len() {
+                    continue;
+                }
+                let needed = inclusion[i + 1..=end]
+                    .iter()
+                    .flatten()
+                    .fold(f64::INFINITY, |a, b| a.min(*b));
+                if needed.is_infinite() {
+                    continue;
+                }
+                let estimate = match &processed {
+                    Ok((fee_rates_by_block, last_block_ts)) => {
+                        let blocks_data = BlocksData::new(fee_rates_by_block, *last_block_ts);
+                        estimator.estimate_fee(*target, timestamp, Some(blocks_data))
+                    }
+                    Err(_) => {
+                        stats.errors += 1;
+                        continue;
+                    }
+                };
+                match estimate {
+                    Ok(estimate) => {
+                        stats.count += 1;
+                        let estimate = estimate as f64;
+                        if estimate >= needed {
+                            stats.hits += 1;
+                            stats.overpayment += estimate - needed;
+                        }
+                    }
+                    Err(_) => stats.errors += 1,
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backtest, TargetStats};
+    use crate::block_files::tests::spend;
+    use crate::estimator::StaticEstimator;
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::{Block, Network};
+
+    /// a chain where the first block funds 20 outputs, every following block spends one of them
+    /// paying `fee_rates[i]` sat/vbytes
+    fn chain(fee_rates: &[u64]) -> Vec<Block> {
+        let mut funding = genesis_block(Network::Bitcoin).txdata[0].clone();
+        funding.output = vec![funding.output[0].clone(); 20];
+        let mut first = genesis_block(Network::Bitcoin);
+        first.txdata.push(funding.clone());
+
+        let mut blocks = vec![first];
+        for (i, fee_rate) in fee_rates.iter().enumerate() {
+            let vsize = spend(&funding, i as u32, 0).get_weight() as u64 / 4;
+            let mut block = genesis_block(Network::Bitcoin);
+            block.header.prev_blockhash = blocks.last().unwrap().block_hash();
+            block.header.time += i as u32 * 600;
+            block
+                .txdata
+                .push(spend(&funding, i as u32, fee_rate * vsize));
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    #[test]
+    fn test_backtest() {
+        let blocks = chain(&[5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 20, 8, 30]);
+        let backtest = Backtest {
+            targets: vec![1, 2],
+            inclusion_percentile: 5.0,
+        };
+        let estimator = StaticEstimator::new(vec![(1, 10.0)]);
+        let result = backtest.run(&estimator, &blocks).unwrap();
+
+        // estimates at blocks 9..=12, the next blocks need 5, 20, 8 and 30 sat/vbytes
+        let one = &result[&1];
+        assert_eq!((one.count, one.hits), (4, 2));
+        assert!((one.mean_overpayment().unwrap() - 3.5).abs() < f64::EPSILON);
+        assert!((one.hit_rate().unwrap() - 0.5).abs() < f64::EPSILON);
+
+        let two = &result[&2];
+        assert_eq!((two.count, two.hits), (3, 3));
+
+        let never = StaticEstimator::new(vec![(1, 1.0)]);
+        let one = &backtest.run(&never, &blocks).unwrap()[&1];
+        assert_eq!((one.count, one.hits), (4, 0));
+        assert_eq!(one.mean_overpayment(), None);
+        assert_eq!(TargetStats::default().hit_rate(), None);
+    }
+
+    #[test]
+    fn test_backtest_skips_bad_windows() {
+        let mut blocks = chain(&[5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 20, 8, 30]);
+        // blocks 1 and 2 not connected, the windows at blocks 9 and 10 can't be processed
+        blocks[2].header.prev_blockhash = Default::default();
+        for i in 3..blocks.len() {
+            blocks[i].header.prev_blockhash = blocks[i - 1].block_hash();
+        }
+        let backtest = Backtest {
+            targets: vec![1],
+            inclusion_percentile: 5.0,
+        };
+        let estimator = StaticEstimator::new(vec![(1, 10.0)]);
+        let one = &backtest.run(&estimator, &blocks).unwrap()[&1];
+        // estimates at blocks 11 and 12, the next blocks need 8 and 30 sat/vbytes
+        assert_eq!((one.count, one.hits, one.errors), (2, 1, 2));
+    }
+}
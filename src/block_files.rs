@@ -0,0 +1,255 @@
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::{Block, BlockHash};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Read a block from a file containing it raw or hex encoded
+pub fn read_block_file<P: AsRef<Path>>(path: P) -> io::Result<Block> {
+    let bytes = fs::read(path)?;
+    let bytes = match std::str::from_utf8(&bytes).ok().map(str::trim) {
+        Some(hex) => Vec::<u8>::from_hex(hex).unwrap_or(bytes),
+        None => bytes,
+    };
+    deserialize(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Parse the blocks contained in the content of a Bitcoin Core `blk*.dat` file, where every
+/// record is made of the 4 bytes network magic, the 4 bytes little endian block length and the
+/// block. Parsing stops at the first zeroed magic, used as padding by Bitcoin Core
+pub fn parse_blk(bytes: &[u8]) -> io::Result<Vec<Block>> {
+    let mut blocks = vec![];
+    let mut pos = 0;
+    while pos + 8 <= bytes.len() {
+        if bytes[pos..pos + 4] == [0u8; 4] {
+            break;
+        }
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&bytes[pos + 4..pos + 8]);
+        let start = pos + 8;
+        let end = start + u32::from_le_bytes(len) as usize;
+        if end > bytes.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated block record",
+            ));
+        }
+        let block = deserialize(&bytes[start..end])
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        blocks.push(block);
+        pos = end;
+    }
+    Ok(blocks)
+}
+
+/// Read the key used by Bitcoin Core since version 28 to obfuscate the `blk*.dat` files,
+/// None if `xor.dat` doesn't exist in the `blocks` directory or the key is all zeros
+pub fn read_xor_key<P: AsRef<Path>>(dir: P) -> io::Result<Option<[u8; 8]>> {
+    match fs::read(dir.as_ref().join("xor.dat")) {
+        Ok(bytes) => {
+            let key: [u8; 8] = bytes
+                .get(..8)
+                .and_then(|k| k.try_into().ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid xor.dat"))?;
+            Ok(Some(key).filter(|k| k != &[0u8; 8]))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read a `blk*.dat` file, removing the obfuscation with `xor_key` if given
+pub fn read_blk_file<P: AsRef<Path>>(path: P, xor_key: Option<[u8; 8]>) -> io::Result<Vec<Block>> {
+    let mut bytes = fs::read(path)?;
+    if let Some(key) = xor_key {
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte ^= key[i % 8];
+        }
+    }
+    parse_blk(&bytes)
+}
+
+/// `blk*.dat` files in the `dir`, sorted by name
+fn blk_paths(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if name.starts_with("blk") && name.ends_with(".dat") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Read every block in the `blk*.dat` files of a Bitcoin Core `blocks` directory
+pub fn read_blocks_dir<P: AsRef<Path>>(dir: P) -> io::Result<Vec<Block>> {
+    let xor_key = read_xor_key(&dir)?;
+    let mut blocks = vec![];
+    for path in blk_paths(dir.as_ref())? {
+        blocks.extend(read_blk_file(path, xor_key)?);
+    }
+    Ok(blocks)
+}
+
+/// Returns the last `n` connected blocks, ordered from the oldest, of a Bitcoin Core `blocks`
+/// directory, ready for [`crate::process_blocks::Transactions::from_blocks`] when `n` is 10.
+/// Files are read from the newest until the chain of the most recent tip is long enough.
+/// Since the block index is not used, the tip is the one with the longest chain among the read
+/// blocks, a stale tip could be chosen if it's in the newest file and its fork is not yet read
+pub fn read_last_blocks<P: AsRef<Path>>(dir: P, n: usize) -> io::Result<Vec<Block>> {
+    let xor_key = read_xor_key(&dir)?;
+    let mut blocks = vec![];
+    for path in blk_paths(dir.as_ref())?.iter().rev() {
+        blocks.extend(read_blk_file(path, xor_key)?);
+        if let Some(last) = last_connected(blocks.clone(), n) {
+            return Ok(last);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("less than {} connected blocks", n),
+    ))
+}
+
+/// Returns the last `n` blocks, ordered from the oldest, of the longest chain formed by `blocks`,
+/// given in any order. None if the longest chain is shorter than `n`
+pub fn last_connected(blocks: Vec<Block>, n: usize) -> Option<Vec<Block>> {
+    let parents: HashSet<BlockHash> = blocks.iter().map(|b| b.header.prev_blockhash).collect();
+    let mut by_hash: HashMap<BlockHash, Block> =
+        blocks.into_iter().map(|b| (b.block_hash(), b)).collect();
+
+    let height = |mut hash: BlockHash, by_hash: &HashMap<BlockHash, Block>| {
+        let mut height = 0usize;
+        while let Some(block) = by_hash.get(&hash) {
+            height += 1;
+            hash = block.header.prev_blockhash;
+        }
+        height
+    };
+    let tip = by_hash
+        .keys()
+        .filter(|h| !parents.contains(*h))
+        .map(|h| (height(*h, &by_hash), *h))
+        .max()?
+        .1;
+
+    let mut result = vec![];
+    let mut hash = tip;
+    while result.len() < n {
+        let block = by_hash.remove(&hash)?;
+        hash = block.header.prev_blockhash;
+        result.push(block);
+    }
+    result.reverse();
+    Some(result)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{last_connected, parse_blk, read_blocks_dir, read_last_blocks, read_xor_key};
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::consensus::serialize;
+    use bitcoin::{Block, Network, OutPoint, Transaction, TxIn, TxOut};
+    use std::fs;
+
+    /// `n` connected blocks starting from genesis
+    pub fn chain(n: usize) -> Vec<Block> {
+        let mut blocks = vec![genesis_block(Network::Bitcoin)];
+        while blocks.len() < n {
+            let mut block = genesis_block(Network::Bitcoin);
+            block.header.prev_blockhash = blocks.last().unwrap().block_hash();
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    /// a transaction spending the output `vout` of `prev` paying `fee`
+    pub fn spend(prev: &Transaction, vout: u32, fee: u64) -> Transaction {
+        let value = prev.output[vout as usize].value - fee;
+        Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(prev.txid(), vout),
+                script_sig: Default::default(),
+                sequence: 0xffffffff,
+                witness: Default::default(),
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Default::default(),
+            }],
+        }
+    }
+
+    pub fn blk(blocks: &[Block]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for block in blocks {
+            let block = serialize(block);
+            bytes.extend(&[0xf9, 0xbe, 0xb4, 0xd9]);
+            bytes.extend(&(block.len() as u32).to_le_bytes());
+            bytes.extend(block);
+        }
+        bytes.extend(&[0u8; 16]);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_blk() {
+        let blocks = chain(3);
+        let parsed = parse_blk(&blk(&blocks)).unwrap();
+        assert_eq!(parsed, blocks);
+
+        let bytes = blk(&blocks);
+        assert!(parse_blk(&bytes[..100]).is_err());
+    }
+
+    #[test]
+    fn test_last_connected() {
+        let blocks = chain(12);
+        let mut shuffled = blocks.clone();
+        shuffled.reverse();
+        shuffled.swap(3, 7);
+
+        // a stale block forking from the 5th
+        let mut stale = genesis_block(Network::Bitcoin);
+        stale.header.prev_blockhash = blocks[4].block_hash();
+        stale.header.nonce += 1;
+        shuffled.push(stale);
+
+        let last = last_connected(shuffled.clone(), 10).unwrap();
+        assert_eq!(&last[..], &blocks[2..]);
+        assert!(last_connected(shuffled, 13).is_none());
+    }
+
+    #[test]
+    fn test_read_last_blocks() {
+        let dir = std::env::temp_dir().join(format!("bfm-blocks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let blocks = chain(14);
+        let key = [1u8, 2, 3, 4, 5, 6, 7, 8];
+        for (i, part) in blocks.chunks(5).enumerate() {
+            let bytes: Vec<u8> = blk(part)
+                .iter()
+                .enumerate()
+                .map(|(i, b)| b ^ key[i % 8])
+                .collect();
+            fs::write(dir.join(format!("blk{:05}.dat", i)), bytes).unwrap();
+        }
+        fs::write(dir.join("rev00000.dat"), [0u8; 8]).unwrap();
+        assert!(read_last_blocks(&dir, 10).is_err());
+
+        fs::write(dir.join("xor.dat"), key).unwrap();
+        assert_eq!(read_xor_key(&dir).unwrap(), Some(key));
+        assert_eq!(read_last_blocks(&dir, 10).unwrap(), &blocks[4..]);
+        assert_eq!(read_blocks_dir(&dir).unwrap(), blocks);
+        assert!(read_last_blocks(&dir, 15).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use crate::process_blocks::process_blocks;
+use crate::Error;
+use bitcoin::{Block, BlockHash};
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+/// A source of blocks, like a Bitcoin Core node
+pub trait BlockSource {
+    fn best_block_hash(&self) -> Result<BlockHash, Error>;
+
+    fn block(&self, hash: &BlockHash) -> Result<Block, Error>;
+
+    /// the last `n` blocks ordered from the oldest
+    fn last_blocks(&self, n: usize) -> Result<Vec<Block>, Error> {
+        let mut hash = self.best_block_hash()?;
+        let mut blocks = Vec::with_capacity(n);
+        while blocks.len() < n {
+            let block = self.block(&hash)?;
+            hash = block.header.prev_blockhash;
+            blocks.push(block);
+        }
+        blocks.reverse();
+        Ok(blocks)
+    }
+
+    /// the fee rates and the last block timestamp as returned by [`process_blocks`] on the last
+    /// 10 blocks, ready for [`crate::FeeModel::estimate`]
+    fn fee_data(&self) -> Result<(Vec<f64>, u32), Error> {
+        let blocks = self.last_blocks(10)?;
+        process_blocks(blocks[..].try_into().unwrap())
+    }
+}
+
+/// the error of a backend receiving an unexpected `what`
+#[cfg(any(feature = "rpc", feature = "esplora", feature = "electrum"))]
+pub(crate) fn invalid(what: &str) -> Error {
+    Error::BlockSource(format!("invalid {}", what))
+}
+
+/// In memory [`BlockSource`], useful for tests
+#[derive(Debug, Default)]
+pub struct MemoryBlockSource {
+    blocks: HashMap<BlockHash, Block>,
+    tip: Option<BlockHash>,
+}
+
+impl MemoryBlockSource {
+    /// the last of `blocks` is the tip
+    pub fn new(blocks: Vec<Block>) -> Self {
+        let mut source = Self::default();
+        for block in blocks {
+            source.push(block);
+        }
+        source
+    }
+
+    /// add `block` as the new tip
+    pub fn push(&mut self, block: Block) {
+        let hash = block.block_hash();
+        self.blocks.insert(hash, block);
+        self.tip = Some(hash);
+    }
+}
+
+impl BlockSource for MemoryBlockSource {
+    fn best_block_hash(&self) -> Result<BlockHash, Error> {
+        self.tip
+            .ok_or_else(|| Error::BlockSource("no blocks".to_string()))
+    }
+
+    fn block(&self, hash: &BlockHash) -> Result<Block, Error> {
+        self.blocks
+            .get(hash)
+            .cloned()
+            .ok_or_else(|| Error::BlockSource(format!("block {} not found", hash)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockSource, MemoryBlockSource};
+    use crate::block_files::tests::chain;
+    use crate::Error;
+
+    #[test]
+    fn test_memory_block_source() {
+        let mut blocks = chain(12);
+        let tx = blocks[3].txdata[0].clone();
+        blocks[3].txdata.push(tx);
+        let source = MemoryBlockSource::new(blocks.clone());
+
+        assert_eq!(source.last_blocks(10).unwrap(), &blocks[2..]);
+        let (_, last_block_ts) = source.fee_data().unwrap();
+        assert_eq!(last_block_ts, blocks[3].header.time);
+        assert!(matches!(source.last_blocks(13), Err(Error::BlockSource(_))));
+        assert!(MemoryBlockSource::default().fee_data().is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::process_blocks::process_blocks;
+use crate::Error;
+use bitcoin::Block;
+use std::collections::VecDeque;
+use std::convert::TryInto;
+
+/// The last 10 connected blocks, updated as new blocks arrive
+#[derive(Debug, Default)]
+pub struct BlockWindow {
+    blocks: VecDeque<Block>,
+}
+
+impl BlockWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add `block` as the new tip, if it doesn't connect to the current tip, as in a reorg, the
+    /// blocks after its parent are dropped, or the whole window if the parent isn't in it.
+    /// Returns `true` if the block connected to the current tip
+    pub fn push(&mut self, block: Block) -> bool {
+        let parent = self
+            .blocks
+            .iter()
+            .rposition(|b| b.block_hash() == block.header.prev_blockhash);
+        let connected = match parent {
+            Some(i) => {
+                let connected = i + 1 == self.blocks.len();
+                self.blocks.truncate(i + 1);
+                connected
+            }
+            None => {
+                let connected = self.blocks.is_empty();
+                self.blocks.clear();
+                connected
+            }
+        };
+        if self.blocks.len() == 10 {
+            self.blocks.pop_front();
+        }
+        self.blocks.push_back(block);
+        connected
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.blocks.len() == 10
+    }
+
+    pub fn tip(&self) -> Option<&Block> {
+        self.blocks.back()
+    }
+
+    /// the output of [`process_blocks`] on the window, `LastTsMissing` if it's not full yet
+    pub fn process(&self) -> Result<(Vec<f64>, u32), Error> {
+        if !self.is_full() {
+            return Err(Error::LastTsMissing);
+        }
+        let blocks: Vec<Block> = self.blocks.iter().cloned().collect();
+        let blocks: &[Block; 10] = blocks[..].try_into().unwrap();
+        process_blocks(blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockWindow;
+    use crate::block_files::tests::chain;
+    use crate::Error;
+
+    #[test]
+    fn test_block_window() {
+        let mut blocks = chain(12);
+        let tx = blocks[2].txdata[0].clone();
+        blocks[2].txdata.push(tx);
+        let mut window = BlockWindow::new();
+        for block in blocks.iter().take(11) {
+            assert!(window.push(block.clone()));
+        }
+        assert!(window.is_full());
+        assert_eq!(window.tip(), Some(&blocks[10]));
+        window.process().unwrap();
+
+        // reorg of the last block
+        let mut fork = blocks[10].clone();
+        fork.header.time += 1;
+        assert!(!window.push(fork.clone()));
+        assert!(window.is_full());
+        assert_eq!(window.tip(), Some(&fork));
+        assert!(!window.push(blocks[10].clone()));
+        assert!(window.push(blocks[11].clone()));
+        assert!(window.is_full());
+        assert_eq!(window.tip(), Some(&blocks[11]));
+
+        // reorg deeper than the last block
+        let mut fork = blocks[8].clone();
+        fork.header.time += 1;
+        assert!(!window.push(fork.clone()));
+        assert_eq!(window.tip(), Some(&fork));
+        assert!(!window.is_full());
+
+        // parent not in the window
+        assert!(!window.push(blocks[0].clone()));
+        assert!(!window.is_full());
+        assert!(matches!(window.process(), Err(Error::LastTsMissing)));
+    }
+}
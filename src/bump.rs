@@ -0,0 +1,143 @@
+use crate::{Error, FeeModel, SizeMarker, MIN_RELAY_FEE};
+use bitcoin::Transaction;
+
+/// BIP125 incremental relay fee rate in sat/vbytes, same default as Bitcoin Core
+pub const INCREMENTAL_RELAY_FEE: f64 = 1.0;
+
+/// An unconfirmed transaction with its unconfirmed ancestors
+pub struct Unconfirmed<'a> {
+    pub tx: &'a Transaction,
+    pub fee: u64,
+    /// unconfirmed ancestors of `tx` with their absolute fees
+    pub parents: Vec<(&'a Transaction, u64)>,
+}
+
+/// Fees needed for an unconfirmed transaction to reach the estimated fee rate
+#[derive(Debug, PartialEq)]
+pub struct Bump {
+    /// estimated fee rate in sat/vbytes for the requested block target
+    pub fee_rate: f32,
+    /// absolute fee of a replacement transaction of the same size
+    pub rbf_fee: u64,
+    /// absolute fee of a child spending the transaction
+    pub cpfp_fee: u64,
+}
+
+// `div_ceil` is not available on the minimum supported rust version
+#[allow(clippy::manual_div_ceil)]
+fn vsize(tx: &Transaction) -> u64 {
+    (tx.get_weight() as u64 + 3) / 4
+}
+
+impl<'a> Unconfirmed<'a> {
+    pub fn vsize(&self) -> u64 {
+        vsize(self.tx)
+    }
+
+    fn parents_vsize(&self) -> u64 {
+        self.parents.iter().map(|(tx, _)| vsize(tx)).sum()
+    }
+
+    fn parents_fee(&self) -> u64 {
+        self.parents.iter().map(|(_, fee)| fee).sum()
+    }
+
+    /// fee rate of the package composed by the transaction and its unconfirmed ancestors
+    pub fn package_fee_rate(&self) -> f64 {
+        let fee = self.fee + self.parents_fee();
+        fee as f64 / (self.vsize() + self.parents_vsize()) as f64
+    }
+
+    /// absolute fee of a replacement with the same size reaching `fee_rate` as a package with the
+    /// unconfirmed ancestors, at least the original fee plus the BIP125 incremental relay fee
+    pub fn rbf_fee(&self, fee_rate: f64) -> u64 {
+        let vsize = self.vsize();
+        let package_vsize = vsize + self.parents_vsize();
+        let package_fee = (fee_rate * package_vsize as f64).ceil() as u64;
+        let target_fee = package_fee.saturating_sub(self.parents_fee());
+        let min_fee = self.fee + (INCREMENTAL_RELAY_FEE * vsize as f64).ceil() as u64;
+
+        target_fee.max(min_fee)
+    }
+
+    /// absolute fee of a child of `child_vsize` vbytes bringing the whole package to `fee_rate`
+    pub fn cpfp_fee(&self, child_vsize: u64, fee_rate: f64) -> u64 {
+        let package_vsize = self.vsize() + self.parents_vsize() + child_vsize;
+        let package_fee = (fee_rate * package_vsize as f64).ceil() as u64;
+        let target_fee = package_fee.saturating_sub(self.fee + self.parents_fee());
+        let min_fee = (MIN_RELAY_FEE * child_vsize as f64).ceil() as u64;
+
+        target_fee.max(min_fee)
+    }
+}
+
+impl<N: SizeMarker, O: SizeMarker> FeeModel<N, O> {
+    /// compute the fees needed to confirm `unconfirmed` within `block_target` either by replacing
+    /// it or by spending it with a child of `child_vsize` vbytes.
+    /// Other parameters are the same as [`FeeModel::estimate`]
+    pub fn bump(
+        &self,
+        unconfirmed: &Unconfirmed,
+        child_vsize: u64,
+        block_target: u16,
+        timestamp: Option<u32>,
+        fee_rates: &[f64],
+        last_block_ts: u32,
+    ) -> Result<Bump, Error> {
+        let fee_rate = self.estimate(block_target, timestamp, fee_rates, last_block_ts)?;
+
+        Ok(Bump {
+            fee_rate,
+            rbf_fee: unconfirmed.rbf_fee(fee_rate as f64),
+            cpfp_fee: unconfirmed.cpfp_fee(child_vsize, fee_rate as f64),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Unconfirmed;
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::Network;
+
+    #[test]
+    fn test_rbf_fee() {
+        let tx = genesis_block(Network::Bitcoin).txdata[0].clone();
+        let vsize = super::vsize(&tx);
+        assert_eq!(vsize, 204);
+
+        let unconfirmed = Unconfirmed {
+            tx: &tx,
+            fee: 204,
+            parents: vec![],
+        };
+        // the incremental relay fee is the lower bound
+        assert_eq!(unconfirmed.rbf_fee(1.0), 408);
+        assert_eq!(unconfirmed.rbf_fee(10.0), 2040);
+
+        // a cheap parent must be paid by the replacement
+        let parent = tx.clone();
+        let unconfirmed = Unconfirmed {
+            tx: &tx,
+            fee: 204,
+            parents: vec![(&parent, 0)],
+        };
+        assert_eq!(unconfirmed.rbf_fee(10.0), 4080);
+    }
+
+    #[test]
+    fn test_cpfp_fee() {
+        let tx = genesis_block(Network::Bitcoin).txdata[0].clone();
+        let parent = tx.clone();
+        let unconfirmed = Unconfirmed {
+            tx: &tx,
+            fee: 204,
+            parents: vec![(&parent, 204)],
+        };
+        assert!((unconfirmed.package_fee_rate() - 1.0).abs() < f64::EPSILON);
+
+        assert_eq!(unconfirmed.cpfp_fee(100, 10.0), 5080 - 408);
+        // already above the target, the child pays only the min relay fee
+        assert_eq!(unconfirmed.cpfp_fee(100, 0.5), 100);
+    }
+}
@@ -0,0 +1,157 @@
+//! Source of the current time used by [`crate::FeeModel`] when the timestamp is not given
+
+/// A source of the current unix timestamp in seconds
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u32;
+}
+
+/// The system clock, the default of [`crate::FeeModel`] with the std feature
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> u32 {
+        chrono::Utc::now().timestamp() as u32
+    }
+}
+
+/// A clock always returning the same timestamp, for tests and for replaying estimates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedClock(pub u32);
+
+impl Clock for FixedClock {
+    fn now(&self) -> u32 {
+        self.0
+    }
+}
+
+/// The fee rate estimated by [`crate::FeeModel::estimate_timestamped`] and the timestamp used
+/// to compute it, either the given one or the one returned by the clock. Estimating again with
+/// `Some(timestamp)` and the same inputs returns the same fee rate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    /// in sat/vbytes
+    pub fee_rate: f32,
+    pub timestamp: u32,
+}
+
+/// The block target returned by [`crate::FeeModel::target_for_fee_rate_timestamped`] and the
+/// timestamp used to compute it, like [`Estimate`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetEstimate {
+    /// `None` if a higher fee rate is needed even for [`crate::MAX_BLOCK_TARGET`]
+    pub block_target: Option<u16>,
+    pub timestamp: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "std")]
+    use super::SystemClock;
+    use super::{Clock, Estimate, FixedClock, TargetEstimate};
+    use crate::model_data::tests::BUCKETS;
+    use crate::{get_model_high, get_model_low, Error, FeeModel};
+
+    #[test]
+    fn test_clock() {
+        assert_eq!(FixedClock(10).now(), 10);
+        #[cfg(feature = "std")]
+        assert!(SystemClock.now() > 1_600_000_000);
+    }
+
+    #[test]
+    fn test_estimate_timestamped() {
+        let ts = 1613708045u32;
+        let model = FeeModel::new(get_model_low(), get_model_high()).with_clock(FixedClock(ts));
+        let estimate = model
+            .estimate_timestamped_with_buckets(6, None, &BUCKETS, ts - 300)
+            .unwrap();
+        let expected = model
+            .estimate_with_buckets(6, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        assert_eq!(
+            estimate,
+            Estimate {
+                fee_rate: expected,
+                timestamp: ts
+            }
+        );
+        assert_eq!(
+            model
+                .estimate_with_buckets(6, None, &BUCKETS, ts - 300)
+                .unwrap(),
+            expected
+        );
+
+        // the given timestamp takes precedence over the clock
+        let later = model
+            .estimate_timestamped_with_buckets(6, Some(ts + 3600), &BUCKETS, ts - 300)
+            .unwrap();
+        assert_eq!(later.timestamp, ts + 3600);
+
+        // replay with a different clock
+        let replayed = FeeModel::new(get_model_low(), get_model_high())
+            .estimate_timestamped_with_buckets(6, Some(estimate.timestamp), &BUCKETS, ts - 300)
+            .unwrap();
+        assert_eq!(replayed, estimate);
+
+        let model = FeeModel::new(get_model_low(), get_model_high()).without_clock();
+        let err = model
+            .estimate_with_buckets(6, None, &BUCKETS, ts - 300)
+            .unwrap_err();
+        assert!(matches!(err, Error::MissingTimestamp));
+        assert_eq!(
+            format!("{}", err),
+            "No timestamp given and no clock configured "
+        );
+    }
+
+    #[test]
+    fn test_target_for_fee_rate_timestamped() {
+        let ts = 1613708045u32;
+        let model = FeeModel::new(get_model_low(), get_model_high()).with_clock(FixedClock(ts));
+        let target = model
+            .target_for_fee_rate_timestamped_with_buckets(f32::MAX, None, &BUCKETS, ts - 300)
+            .unwrap();
+        assert_eq!(
+            target,
+            TargetEstimate {
+                block_target: Some(1),
+                timestamp: ts
+            }
+        );
+        let fee_rate = model
+            .estimate_with_buckets(6, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        let expected = model
+            .target_for_fee_rate_with_buckets(fee_rate, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        let target = model
+            .target_for_fee_rate_timestamped_with_buckets(fee_rate, None, &BUCKETS, ts - 300)
+            .unwrap();
+        assert_eq!(target.block_target, expected);
+        assert_eq!(target.timestamp, ts);
+    }
+
+    #[test]
+    fn test_estimate_quantile_timestamped() {
+        let ts = 1613708045u32;
+        let quantile_model = crate::get_model_test_quantile_model;
+        let model = FeeModel::new(quantile_model(), quantile_model()).with_clock(FixedClock(ts));
+        let estimate = model
+            .estimate_quantile_timestamped_with_buckets(6, 0.6, None, &BUCKETS, ts - 300)
+            .unwrap();
+        let expected = model
+            .estimate_quantile_with_buckets(6, 0.6, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        assert_eq!(
+            estimate,
+            Estimate {
+                fee_rate: expected,
+                timestamp: ts
+            }
+        );
+    }
+}
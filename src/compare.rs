@@ -0,0 +1,164 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::estimator::percentile;
+use crate::{Error, FeeModel, SizeMarker};
+
+/// Distribution of a set of estimates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub min: f64,
+    pub p10: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl Summary {
+    /// None if `values` is empty
+    pub fn new(values: &[f64]) -> Option<Self> {
+        Some(Summary {
+            min: percentile(values, 0.0)?,
+            p10: percentile(values, 10.0)?,
+            median: percentile(values, 50.0)?,
+            p90: percentile(values, 90.0)?,
+            max: percentile(values, 100.0)?,
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+        })
+    }
+}
+
+/// Comparison of the estimates of two models over the same inputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub count: usize,
+    pub a: Summary,
+    pub b: Summary,
+    /// mean of `b - a`
+    pub mean_diff: f64,
+    /// mean of `|b - a|`
+    pub mean_abs_diff: f64,
+    pub max_abs_diff: f64,
+    /// Pearson correlation, NaN if the estimates of one of the models are constant
+    pub correlation: f64,
+}
+
+impl Comparison {
+    /// None if `a` is empty, returns [`Error::InvalidInput`] if `a` and `b` have different lengths
+    pub fn new(a: &[f64], b: &[f64]) -> Result<Option<Self>, Error> {
+        if a.len() != b.len() {
+            return Err(Error::InvalidInput(format!(
+                "{} estimates compared with {}",
+                a.len(),
+                b.len()
+            )));
+        }
+        let n = a.len() as f64;
+        let (summary_a, summary_b) = match (Summary::new(a), Summary::new(b)) {
+            (Some(summary_a), Some(summary_b)) => (summary_a, summary_b),
+            _ => return Ok(None),
+        };
+        let diffs: Vec<f64> = a.iter().zip(b).map(|(a, b)| b - a).collect();
+
+        let mut cov = 0.0;
+        let mut var_a = 0.0;
+        let mut var_b = 0.0;
+        for (a, b) in a.iter().zip(b) {
+            let da = a - summary_a.mean;
+            let db = b - summary_b.mean;
+            cov += da * db;
+            var_a += da * da;
+            var_b += db * db;
+        }
+
+        Ok(Some(Comparison {
+            count: a.len(),
+            mean_diff: diffs.iter().sum::<f64>() / n,
+            mean_abs_diff: diffs.iter().map(|d| d.abs()).sum::<f64>() / n,
+            max_abs_diff: diffs.iter().fold(0.0, |acc, d| d.abs().max(acc)),
+            correlation: cov / (var_a * var_b).sqrt(),
+            a: summary_a,
+            b: summary_b,
+        }))
+    }
+}
+
+/// Compare the estimates of models `a` and `b` over `dataset`, containing the features as
+/// extracted by [`FeeModel::estimate_with_buckets`].
+/// Returns the comparison over the whole dataset and the one for every `confirms_in` value.
+pub fn compare<N1, O1, N2, O2>(
+    a: &FeeModel<N1, O1>,
+    b: &FeeModel<N2, O2>,
+    dataset: &[HashMap<String, f32>],
+) -> Result<(Option<Comparison>, BTreeMap<u16, Comparison>), Error>
+where
+    N1: SizeMarker,
+    O1: SizeMarker,
+    N2: SizeMarker,
+    O2: SizeMarker,
+{
+    let mut all = (vec![], vec![]);
+    let mut per_target: BTreeMap<u16, (Vec<f64>, Vec<f64>)> = BTreeMap::new();
+    for features in dataset {
+        let estimate_a = a.estimate_features(features)? as f64;
+        let estimate_b = b.estimate_features(features)? as f64;
+        let target = features.get("confirms_in").copied().unwrap_or(0.0) as u16;
+        let entry = per_target.entry(target).or_default();
+        entry.0.push(estimate_a);
+        entry.1.push(estimate_b);
+        all.0.push(estimate_a);
+        all.1.push(estimate_b);
+    }
+
+    let mut comparisons = BTreeMap::new();
+    for (target, (a, b)) in per_target {
+        if let Some(comparison) = Comparison::new(&a, &b)? {
+            comparisons.insert(target, comparison);
+        }
+    }
+    Ok((Comparison::new(&all.0, &all.1)?, comparisons))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, Comparison};
+    use crate::model_data::tests::get_test_pre_norm;
+    use crate::Error;
+    use crate::{get_model_high, get_model_low, get_model_test_model, FeeModel};
+
+    #[test]
+    fn test_comparison() {
+        let c = Comparison::new(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0])
+            .unwrap()
+            .unwrap();
+        assert_eq!(c.count, 3);
+        assert!((c.correlation - 1.0).abs() < 1e-9);
+        assert!((c.mean_diff - 2.0).abs() < 1e-9);
+        assert!((c.max_abs_diff - 3.0).abs() < 1e-9);
+        assert_eq!(c.b.median, 4.0);
+        assert!(Comparison::new(&[], &[]).unwrap().is_none());
+        assert!(matches!(
+            Comparison::new(&[1.0], &[]),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_compare() {
+        let a = FeeModel::new(get_model_low(), get_model_high());
+        let b = FeeModel::new(get_model_test_model(), get_model_test_model());
+        let mut dataset = vec![];
+        for target in [1u16, 2, 6].iter() {
+            let mut features = get_test_pre_norm();
+            features.insert("confirms_in".to_string(), *target as f32);
+            dataset.push(features);
+        }
+
+        let (all, per_target) = compare(&a, &a, &dataset).unwrap();
+        assert_eq!(all.unwrap().max_abs_diff, 0.0);
+        assert_eq!(per_target.len(), 3);
+
+        let (all, _) = compare(&a, &b, &dataset).unwrap();
+        assert_eq!(all.unwrap().count, 3);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::process_blocks::process_blocks;
+use crate::{fee_buckets, Error, Features, FEATURES, MAX_BLOCK_TARGET, TIME_FEATURES};
+use bitcoin::{Block, Txid};
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::{self, Write};
+
+/// name of the label column of the csv written by [`write_csv`]
+pub const LABEL: &str = "fee_rate";
+
+/// A transaction seen in the mempool at `timestamp` paying `fee_rate` sat/vbytes
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolObservation {
+    pub txid: Txid,
+    pub timestamp: u32,
+    pub fee_rate: f64,
+}
+
+/// A training example: the features as computed by [`crate::FeeModel::estimate`] at the time
+/// of the observation and the fee rate paid by the transaction confirmed in `confirms_in` blocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub features: HashMap<String, f32>,
+    pub fee_rate: f64,
+}
+
+/// Build the dataset from the connected `blocks`, ordered from the oldest, and the transactions
+/// seen in the mempool.
+/// The tip at the time of the observation is the last block with an header time not greater than
+/// the observation timestamp. Observations are skipped if the tip hasn't 9 previous blocks, if
+/// the transaction is not confirmed in a following block within [`MAX_BLOCK_TARGET`] or if the
+/// last 10 blocks have only coinbase transactions
+pub fn build(blocks: &[Block], observations: &[MempoolObservation]) -> Result<Vec<Row>, Error> {
+    build_with_utc_offset(blocks, observations, 0)
+}
+
+/// same as [`build`] with the time features in the timezone `utc_offset` seconds east of UTC,
+/// the models trained on it must have the same [`crate::ModelData::utc_offset`]
+pub fn build_with_utc_offset(
+    blocks: &[Block],
+    observations: &[MempoolObservation],
+    utc_offset: i32,
+) -> Result<Vec<Row>, Error> {
+    let mut confirmed = HashMap::new();
+    for (i, block) in blocks.iter().enumerate() {
+        for tx in block.txdata.iter() {
+            confirmed.insert(tx.txid(), i);
+        }
+    }
+    // header times are not monotonic, the running max is
+    let mut max_times = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        let max = max_times
+            .last()
+            .cloned()
+            .unwrap_or(0)
+            .max(block.header.time);
+        max_times.push(max);
+    }
+
+    let mut windows: HashMap<usize, Option<(Vec<u64>, u32)>> = HashMap::new();
+    let mut rows = vec![];
+    for observation in observations {
+        // the number of blocks mined at the time of the observation, `partition_point` is not
+        // available on the minimum supported rust version
+        let mined = max_times.binary_search_by(|t| match *t <= observation.timestamp {
+            true => Ordering::Less,
+            false => Ordering::Greater,
+        });
+        let tip = match mined.unwrap_or_else(|i| i) {
+            0 => continue,
+            next => next - 1,
+        };
+        let confirms_in = match confirmed.get(&observation.txid) {
+            Some(height) if *height > tip && height - tip <= MAX_BLOCK_TARGET as usize => {
+                height - tip
+            }
+            _ => continue,
+        };
+        if tip < 9 {
+            continue;
+        }
+        let processed = match windows.entry(tip) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let window = blocks[tip - 9..=tip].try_into().unwrap();
+                entry.insert(match process_blocks(window) {
+                    Ok((fee_rates, last_block_ts)) => {
+                        Some((fee_buckets(&fee_rates), last_block_ts))
+                    }
+                    Err(Error::LastTsMissing) => None,
+                    Err(e) => return Err(e),
+                })
+            }
+        };
+        if let Some((buckets, last_block_ts)) = processed {
+            let features = Features::with_utc_offset(
+                confirms_in as u16,
+                observation.timestamp,
+                buckets,
+                *last_block_ts,
+                utc_offset,
+            );
+            rows.push(Row {
+                features: features.to_map(),
+                fee_rate: observation.fee_rate,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+/// Write `rows` as csv with [`FEATURES`], [`TIME_FEATURES`] and [`LABEL`] as header
+pub fn write_csv<W: Write>(rows: &[Row], mut writer: W) -> io::Result<()> {
+    writeln!(
+        writer,
+        "{},{},{}",
+        FEATURES.join(","),
+        TIME_FEATURES.join(","),
+        LABEL
+    )?;
+    for row in rows {
+        let values: Vec<String> = FEATURES
+            .iter()
+            .chain(TIME_FEATURES.iter())
+            .map(|c| row.features.get(*c).cloned().unwrap_or(0.0).to_string())
+            .chain(std::iter::once(row.fee_rate.to_string()))
+            .collect();
+        writeln!(writer, "{}", values.join(","))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build, build_with_utc_offset, write_csv, MempoolObservation};
+    use crate::block_files::tests::{chain, spend};
+    use crate::process_blocks::process_blocks;
+    use crate::{get_model_high, get_model_low, FeeModel};
+    use bitcoin::Block;
+    use std::convert::TryInto;
+
+    /// 14 blocks 10 minutes apart, the second funds a transaction in every following block
+    fn blocks() -> Vec<Block> {
+        let mut blocks = chain(14);
+        let mut funding = blocks[0].txdata[0].clone();
+        funding.output = vec![funding.output[0].clone(); 14];
+        blocks[1].txdata.push(funding.clone());
+        for i in 2..blocks.len() {
+            blocks[i]
+                .txdata
+                .push(spend(&funding, i as u32, i as u64 * 1000));
+            blocks[i].header.time = blocks[0].header.time + i as u32 * 600;
+            blocks[i].header.prev_blockhash = blocks[i - 1].block_hash();
+        }
+        blocks
+    }
+
+    #[test]
+    fn test_build() {
+        let blocks = blocks();
+        let genesis_time = blocks[0].header.time;
+        let observation = |block: usize, seen: u32| MempoolObservation {
+            txid: blocks[block].txdata[1].txid(),
+            timestamp: genesis_time + seen,
+            fee_rate: 12.5,
+        };
+        let observations = vec![
+            // seen 5 minutes after block 9, confirmed in 12
+            observation(12, 9 * 600 + 300),
+            // seen before having 10 blocks
+            observation(12, 8 * 600 + 300),
+            // seen after being confirmed
+            observation(10, 11 * 600),
+            // confirmed in the next block, the tip is the last one with time not greater
+            observation(13, 12 * 600),
+        ];
+        let rows = build(&blocks, &observations).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].features["confirms_in"], 3.0);
+        assert_eq!(rows[0].features["delta_last"], 9.0 * 600.0 + 300.0);
+        assert_eq!(rows[0].fee_rate, 12.5);
+        assert_eq!(rows[1].features["confirms_in"], 1.0);
+
+        // same features used by the estimation
+        let model = FeeModel::new(get_model_low(), get_model_high());
+        let (fee_rates, last_block_ts) = process_blocks(blocks[..10].try_into().unwrap()).unwrap();
+        let estimate = model
+            .estimate(
+                3,
+                Some(genesis_time + 9 * 600 + 300),
+                &fee_rates,
+                last_block_ts,
+            )
+            .unwrap();
+        assert_eq!(
+            model.estimate_features(&rows[0].features).unwrap(),
+            estimate
+        );
+
+        let shifted = build_with_utc_offset(&blocks, &observations, 3600).unwrap();
+        assert_eq!(
+            shifted[0].features["hour"],
+            (rows[0].features["hour"] + 1.0) % 24.0
+        );
+        assert_eq!(shifted[0].features["delta_last"], 9.0 * 600.0 + 300.0);
+
+        let mut csv = vec![];
+        write_csv(&rows, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        let header = lines.next().unwrap();
+        assert!(header.starts_with("confirms_in,b0,"));
+        assert!(header.contains(",hour,minute_of_day,minute_of_day_sin,"));
+        assert!(header.ends_with(",month,fee_rate"));
+        assert!(lines.next().unwrap().starts_with("3,"));
+        assert!(lines.next().unwrap().ends_with(",12.5"));
+    }
+}
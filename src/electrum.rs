@@ -0,0 +1,294 @@
+use crate::block_source::{invalid, BlockSource};
+use crate::Error;
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::{Block, BlockHash, BlockHeader, Transaction};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+
+/// positions requested in a single batch with `blockchain.transaction.id_from_pos`
+const POS_BATCH: usize = 100;
+
+/// [`BlockSource`] backed by an Electrum server over plain TCP, like electrs or ElectrumX on a
+/// trusted network, TLS is not supported.
+/// Electrum doesn't serve blocks, they are rebuilt from the header and the transactions found
+/// by position and checked against the merkle root, so every block costs many requests
+pub struct ElectrumBlockSource {
+    conn: Mutex<(BufReader<TcpStream>, usize)>,
+    /// heights of the known block hashes, since Electrum requests blocks by height
+    heights: Mutex<HashMap<BlockHash, u32>>,
+}
+
+impl ElectrumBlockSource {
+    /// `addr` like `127.0.0.1:50001`
+    pub fn new(addr: &str) -> Result<Self, Error> {
+        let io = |e: std::io::Error| Error::BlockSource(e.to_string());
+        let stream = TcpStream::connect(addr).map_err(io)?;
+        // every batch waits for the responses, don't delay the requests
+        stream.set_nodelay(true).map_err(io)?;
+        Ok(ElectrumBlockSource {
+            conn: Mutex::new((BufReader::new(stream), 0)),
+            heights: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// send `calls` in a single JSON-RPC batch, returning the result or the error of each call
+    pub fn batch(&self, calls: &[(&str, Value)]) -> Result<Vec<Result<Value, String>>, Error> {
+        let io = |e: std::io::Error| Error::BlockSource(e.to_string());
+        let mut conn = self.conn.lock().unwrap();
+        let (reader, next_id) = &mut *conn;
+        let first_id = *next_id;
+        let requests: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(i, (method, params))| {
+                json!({"jsonrpc": "2.0", "id": first_id + i, "method": method, "params": params})
+            })
+            .collect();
+        *next_id += calls.len();
+        let mut line = Value::Array(requests).to_string();
+        line.push('\n');
+        reader.get_mut().write_all(line.as_bytes()).map_err(io)?;
+
+        let mut results: Vec<Option<Result<Value, String>>> = vec![None; calls.len()];
+        while results.iter().any(Option::is_none) {
+            let mut line = String::new();
+            if reader.read_line(&mut line).map_err(io)? == 0 {
+                return Err(Error::BlockSource("connection closed".to_string()));
+            }
+            let responses = match serde_json::from_str(&line) {
+                Ok(Value::Array(responses)) => responses,
+                Ok(response) => vec![response],
+                Err(e) => return Err(Error::BlockSource(e.to_string())),
+            };
+            // notifications, like new headers after `blockchain.headers.subscribe`, have no id
+            for mut response in responses {
+                let index = match response["id"].as_u64() {
+                    Some(id) if id as usize >= first_id => id as usize - first_id,
+                    _ => continue,
+                };
+                if let Some(result) = results.get_mut(index) {
+                    *result = Some(match response["error"].take() {
+                        Value::Null => Ok(response["result"].take()),
+                        error => Err(error.to_string()),
+                    });
+                }
+            }
+        }
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, Error> {
+        self.batch(&[(method, params)])?
+            .remove(0)
+            .map_err(Error::BlockSource)
+    }
+
+    fn txids(&self, height: u32) -> Result<Vec<String>, Error> {
+        let mut txids = vec![];
+        loop {
+            let calls: Vec<_> = (txids.len()..txids.len() + POS_BATCH)
+                .map(|pos| ("blockchain.transaction.id_from_pos", json!([height, pos])))
+                .collect();
+            for result in self.batch(&calls)? {
+                match result {
+                    Ok(Value::String(txid)) => txids.push(txid),
+                    // the position after the last transaction
+                    Err(_) if !txids.is_empty() => return Ok(txids),
+                    Err(e) => return Err(Error::BlockSource(e)),
+                    Ok(_) => return Err(invalid("txid")),
+                }
+            }
+        }
+    }
+
+    /// the block at `height`, the known hashes are updated with its parent
+    pub fn block_at(&self, height: u32) -> Result<Block, Error> {
+        let header = self.call("blockchain.block.header", json!([height]))?;
+        let header: BlockHeader = decode(&header).ok_or_else(|| invalid("header"))?;
+
+        let txids = self.txids(height)?;
+        let calls: Vec<_> = txids
+            .iter()
+            .map(|txid| ("blockchain.transaction.get", json!([txid])))
+            .collect();
+        let mut txdata = vec![];
+        for result in self.batch(&calls)? {
+            let tx: Transaction = decode(&result.map_err(Error::BlockSource)?)
+                .ok_or_else(|| invalid("transaction"))?;
+            txdata.push(tx);
+        }
+        let block = Block { header, txdata };
+        if !block.check_merkle_root() {
+            return Err(invalid("merkle root"));
+        }
+        if let Some(prev_height) = height.checked_sub(1) {
+            let mut heights = self.heights.lock().unwrap();
+            heights.insert(block.header.prev_blockhash, prev_height);
+        }
+        Ok(block)
+    }
+}
+
+fn decode<T: bitcoin::consensus::Decodable>(hex: &Value) -> Option<T> {
+    let bytes = Vec::<u8>::from_hex(hex.as_str()?).ok()?;
+    deserialize(&bytes).ok()
+}
+
+impl BlockSource for ElectrumBlockSource {
+    fn best_block_hash(&self) -> Result<BlockHash, Error> {
+        let tip = self.call("blockchain.headers.subscribe", json!([]))?;
+        let header: BlockHeader = decode(&tip["hex"]).ok_or_else(|| invalid("header"))?;
+        let height = tip["height"].as_u64().ok_or_else(|| invalid("height"))?;
+        let hash = header.block_hash();
+        self.heights.lock().unwrap().insert(hash, height as u32);
+        Ok(hash)
+    }
+
+    /// only blocks already met as the tip or as the parent of a returned block are known
+    fn block(&self, hash: &BlockHash) -> Result<Block, Error> {
+        let height = self.heights.lock().unwrap().get(hash).cloned();
+        let height =
+            height.ok_or_else(|| Error::BlockSource(format!("unknown height of {}", hash)))?;
+        let block = self.block_at(height)?;
+        if &block.block_hash() != hash {
+            return Err(Error::BlockSource(format!("block {} reorged", hash)));
+        }
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ElectrumBlockSource;
+    use crate::block_files::tests::{chain, spend};
+    use crate::block_source::BlockSource;
+    use crate::process_blocks::process_blocks;
+    use bitcoin::consensus::serialize;
+    use bitcoin::hashes::hex::ToHex;
+    use bitcoin::hashes::{sha256d, Hash};
+    use bitcoin::{Block, TxMerkleNode};
+    use serde_json::{json, Value};
+    use std::convert::TryInto;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    fn merkle_root(block: &Block) -> TxMerkleNode {
+        let mut layer: Vec<[u8; 32]> = block
+            .txdata
+            .iter()
+            .map(|tx| tx.txid().into_inner())
+            .collect();
+        while layer.len() > 1 {
+            layer = layer
+                .chunks(2)
+                .map(|pair| {
+                    let right = pair.get(1).unwrap_or(&pair[0]);
+                    sha256d::Hash::hash(&[pair[0], *right].concat()).into_inner()
+                })
+                .collect();
+        }
+        TxMerkleNode::from_inner(layer[0])
+    }
+
+    /// 11 blocks, the second funds the transactions of the following ones
+    fn blocks() -> Vec<Block> {
+        let mut blocks = chain(11);
+        let mut funding = blocks[0].txdata[0].clone();
+        funding.output = vec![funding.output[0].clone(); 5];
+        blocks[1].txdata.push(funding.clone());
+        for (vout, (i, fee)) in [(3, 1000), (4, 3000), (8, 250)].iter().enumerate() {
+            blocks[*i].txdata.push(spend(&funding, vout as u32, *fee));
+        }
+        let child = spend(&blocks[3].txdata[1], 0, 500);
+        blocks[9].txdata.push(child);
+        for i in 0..blocks.len() {
+            if i > 0 {
+                blocks[i].header.prev_blockhash = blocks[i - 1].block_hash();
+            }
+            blocks[i].header.merkle_root = merkle_root(&blocks[i]);
+        }
+        blocks
+    }
+
+    /// Electrum server serving `blocks` at heights 0.., `skip_last_tx` simulates a server
+    /// missing a transaction
+    fn serve(blocks: Vec<Block>, skip_last_tx: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let response = move |request: &Value| {
+            let params = &request["params"];
+            let block = |i: &Value| blocks.get(i.as_u64().unwrap() as usize);
+            let result = match request["method"].as_str().unwrap() {
+                "blockchain.headers.subscribe" => {
+                    let tip = blocks.last().unwrap();
+                    Some(
+                        json!({"height": blocks.len() - 1, "hex": serialize(&tip.header).to_hex()}),
+                    )
+                }
+                "blockchain.block.header" => {
+                    block(&params[0]).map(|b| json!(serialize(&b.header).to_hex()))
+                }
+                "blockchain.transaction.id_from_pos" => block(&params[0]).and_then(|b| {
+                    let count = b.txdata.len() - (skip_last_tx && b.txdata.len() > 1) as usize;
+                    let pos = params[1].as_u64().unwrap() as usize;
+                    b.txdata[..count]
+                        .get(pos)
+                        .map(|tx| json!(tx.txid().to_string()))
+                }),
+                "blockchain.transaction.get" => blocks
+                    .iter()
+                    .flat_map(|b| b.txdata.iter())
+                    .find(|tx| tx.txid().to_string() == params[0].as_str().unwrap())
+                    .map(|tx| json!(serialize(tx).to_hex())),
+                _ => None,
+            };
+            match result {
+                Some(result) => json!({"jsonrpc": "2.0", "id": request["id"], "result": result}),
+                None => {
+                    let error = json!({"code": 1, "message": "not found"});
+                    json!({"jsonrpc": "2.0", "id": request["id"], "error": error})
+                }
+            }
+        };
+        std::thread::spawn(move || {
+            let stream = listener.incoming().next().unwrap().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            for line in BufReader::new(stream).lines() {
+                let requests: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                let responses: Vec<Value> =
+                    requests.as_array().unwrap().iter().map(&response).collect();
+                // a notification before the responses
+                let notification = json!({"jsonrpc": "2.0", "method": "blockchain.headers.subscribe", "params": []});
+                let lines = format!("{}\n{}\n", notification, Value::Array(responses));
+                writer.write_all(lines.as_bytes()).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_electrum_fee_data() {
+        let blocks = blocks();
+        let source = ElectrumBlockSource::new(&serve(blocks.clone(), false)).unwrap();
+        assert_eq!(source.last_blocks(10).unwrap(), &blocks[1..]);
+
+        let (mut fee_rates, last_block_ts) = source.fee_data().unwrap();
+        let (mut expected, expected_ts) = process_blocks(blocks[1..].try_into().unwrap()).unwrap();
+        fee_rates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(fee_rates.len(), 4);
+        assert_eq!(fee_rates, expected);
+        assert_eq!(last_block_ts, expected_ts);
+    }
+
+    #[test]
+    fn test_electrum_missing_tx() {
+        let source = ElectrumBlockSource::new(&serve(blocks(), true)).unwrap();
+        let err = source.fee_data().unwrap_err().to_string();
+        assert!(err.contains("invalid merkle root"), "{}", err);
+    }
+}
@@ -1,4 +1,5 @@
-use std::fmt;
+use alloc::string::String;
+use core::fmt;
 
 #[derive(Debug)]
 pub enum Error {
@@ -6,6 +7,14 @@ pub enum Error {
     MissingStdData(String),
     UnconnectedBlocks,
     LastTsMissing,
+    MissingQuantiles,
+    QuantileOutOfRange(f32),
+    MissingFeeRates,
+    InvalidModel(String),
+    BlockSource(String),
+    InvalidDataset(String),
+    InvalidInput(String),
+    MissingTimestamp,
 }
 
 impl fmt::Display for Error {
@@ -15,8 +24,19 @@ impl fmt::Display for Error {
             Error::MissingStdData(s) => write!(f, "Missing std field {} ", s),
             Error::UnconnectedBlocks => write!(f, "Supplied blocks must be ordered and connected "),
             Error::LastTsMissing => write!(f, "None of the 10 blocks is"),
+            Error::MissingQuantiles => write!(f, "The model does not output quantiles "),
+            Error::QuantileOutOfRange(p) => {
+                write!(f, "Probability {} is outside the model quantiles ", p)
+            }
+            Error::MissingFeeRates => write!(f, "No fee rates available "),
+            Error::InvalidModel(s) => write!(f, "Invalid model: {} ", s),
+            Error::BlockSource(s) => write!(f, "Block source error: {} ", s),
+            Error::InvalidDataset(s) => write!(f, "Invalid dataset: {} ", s),
+            Error::InvalidInput(s) => write!(f, "Invalid input: {} ", s),
+            Error::MissingTimestamp => write!(f, "No timestamp given and no clock configured "),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for Error {}
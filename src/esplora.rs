@@ -0,0 +1,119 @@
+use crate::block_source::{invalid, BlockSource};
+use crate::Error;
+use bitcoin::consensus::deserialize;
+use bitcoin::{Block, BlockHash};
+use std::io::Read;
+use std::str::FromStr;
+
+/// [`BlockSource`] backed by the Esplora HTTP API, like `https://blockstream.info/api`, fetching
+/// every block with a single `/block/:hash/raw` request
+pub struct EsploraBlockSource {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl EsploraBlockSource {
+    /// `url` is the base of the API without the trailing slash
+    pub fn new(url: &str) -> Self {
+        EsploraBlockSource {
+            url: url.trim_end_matches('/').to_string(),
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    fn get(&self, path: &str) -> Result<ureq::Response, Error> {
+        match self.agent.get(&format!("{}{}", self.url, path)).call() {
+            Ok(response) => Ok(response),
+            Err(ureq::Error::Status(status, response)) => {
+                let body = response.into_string().unwrap_or_default();
+                Err(Error::BlockSource(format!("{} status {}", body, status)))
+            }
+            Err(e) => Err(Error::BlockSource(e.to_string())),
+        }
+    }
+}
+
+impl BlockSource for EsploraBlockSource {
+    fn best_block_hash(&self) -> Result<BlockHash, Error> {
+        let hash = self
+            .get("/blocks/tip/hash")?
+            .into_string()
+            .map_err(|e| Error::BlockSource(e.to_string()))?;
+        BlockHash::from_str(hash.trim()).map_err(|_| invalid("block hash"))
+    }
+
+    fn block(&self, hash: &BlockHash) -> Result<Block, Error> {
+        let mut bytes = vec![];
+        self.get(&format!("/block/{}/raw", hash))?
+            .into_reader()
+            .read_to_end(&mut bytes)
+            .map_err(|e| Error::BlockSource(e.to_string()))?;
+        deserialize(&bytes).map_err(|_| invalid("block"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EsploraBlockSource;
+    use crate::block_files::tests::{chain, spend};
+    use crate::block_source::BlockSource;
+    use crate::http_stub::serve;
+    use crate::process_blocks::process_blocks;
+    use bitcoin::consensus::serialize;
+    use std::convert::TryInto;
+
+    #[test]
+    fn test_esplora_fee_data() {
+        let mut blocks = chain(10);
+        let coinbase = blocks[0].txdata[0].clone();
+        let funding = spend(&coinbase, 0, 1000);
+        blocks[3].txdata.push(funding.clone());
+        blocks[6].txdata.push(spend(&funding, 0, 2000));
+        for i in 1..10 {
+            blocks[i].header.prev_blockhash = blocks[i - 1].block_hash();
+        }
+        let (mut expected, expected_ts) = process_blocks(blocks[..].try_into().unwrap()).unwrap();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let url = serve(move |request| {
+            let tip = blocks[9].block_hash();
+            if request.path == "/blocks/tip/hash" {
+                return (200, tip.to_string().into_bytes());
+            }
+            match blocks
+                .iter()
+                .find(|b| request.path == format!("/block/{}/raw", b.block_hash()))
+            {
+                Some(block) => (200, serialize(block)),
+                None => (404, b"Block not found".to_vec()),
+            }
+        });
+
+        let source = EsploraBlockSource::new(&format!("{}/", url));
+        let (mut fee_rates, last_block_ts) = source.fee_data().unwrap();
+        fee_rates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(fee_rates, expected);
+        assert_eq!(fee_rates.len(), 2);
+        assert_eq!(last_block_ts, expected_ts);
+    }
+
+    #[test]
+    fn test_esplora_block() {
+        let blocks = chain(2);
+        let tip = blocks[1].clone();
+        let url = serve(move |request| {
+            if request.path == "/blocks/tip/hash" {
+                (200, tip.block_hash().to_string().into_bytes())
+            } else if request.path == format!("/block/{}/raw", tip.block_hash()) {
+                (200, serialize(&tip))
+            } else {
+                (404, b"Block not found".to_vec())
+            }
+        });
+
+        let source = EsploraBlockSource::new(&url);
+        let hash = source.best_block_hash().unwrap();
+        assert_eq!(source.block(&hash).unwrap(), blocks[1]);
+        let err = source.last_blocks(2).unwrap_err().to_string();
+        assert!(err.contains("Block not found status 404"), "{}", err);
+    }
+}
@@ -0,0 +1,338 @@
+use crate::{Error, FeeModel, SizeMarker};
+
+/// The last blocks as seen by the estimators
+#[derive(Debug, Clone, Copy)]
+pub struct BlocksData<'a> {
+    /// fee rates in sat/vbytes of the transactions of every block, from the oldest, as returned
+    /// by `process_blocks_by_block`
+    pub fee_rates_by_block: &'a [Vec<f64>],
+    /// timestamp of the last block
+    pub last_block_ts: u32,
+}
+
+impl<'a> BlocksData<'a> {
+    pub fn new(fee_rates_by_block: &'a [Vec<f64>], last_block_ts: u32) -> Self {
+        BlocksData {
+            fee_rates_by_block,
+            last_block_ts,
+        }
+    }
+
+    /// the fee rates of all the blocks, as returned by `process_blocks`
+    pub fn fee_rates(&self) -> Vec<f64> {
+        self.fee_rates_by_block.concat()
+    }
+}
+
+/// A source of fee rate estimations in sat/vbytes
+pub trait FeeEstimator {
+    /// estimate the fee rate to confirm within `block_target`, `timestamp` if None it's
+    /// initialized to current time. `blocks_data` is None if recent blocks are not available
+    fn estimate_fee(
+        &self,
+        block_target: u16,
+        timestamp: Option<u32>,
+        blocks_data: Option<BlocksData>,
+    ) -> Result<f32, Error>;
+
+    /// a short name identifying the estimator
+    fn name(&self) -> &str;
+}
+
+impl<N: SizeMarker, O: SizeMarker> FeeEstimator for FeeModel<N, O> {
+    fn estimate_fee(
+        &self,
+        block_target: u16,
+        timestamp: Option<u32>,
+        blocks_data: Option<BlocksData>,
+    ) -> Result<f32, Error> {
+        let blocks_data = blocks_data.ok_or(Error::MissingFeeRates)?;
+        self.estimate(
+            block_target,
+            timestamp,
+            &blocks_data.fee_rates(),
+            blocks_data.last_block_ts,
+        )
+    }
+
+    fn name(&self) -> &str {
+        "model"
+    }
+}
+
+/// Estimator returning fixed fee rates
+pub struct StaticEstimator {
+    table: Vec<(u16, f32)>,
+}
+
+impl StaticEstimator {
+    /// `table` contains `(block_target, fee_rate)` pairs, the fee rate of the first entry having
+    /// a block target greater or equal than the requested one is returned, or the last one if
+    /// none is.
+    pub fn new(mut table: Vec<(u16, f32)>) -> Self {
+        table.sort_by_key(|(target, _)| *target);
+        StaticEstimator { table }
+    }
+}
+
+impl FeeEstimator for StaticEstimator {
+    fn estimate_fee(
+        &self,
+        block_target: u16,
+        _timestamp: Option<u32>,
+        _blocks_data: Option<BlocksData>,
+    ) -> Result<f32, Error> {
+        self.table
+            .iter()
+            .find(|(target, _)| *target >= block_target)
+            .or_else(|| self.table.last())
+            .map(|(_, fee_rate)| *fee_rate)
+            .ok_or(Error::MissingFeeRates)
+    }
+
+    fn name(&self) -> &str {
+        "static"
+    }
+}
+
+/// Lookup entry of [`PercentileEstimator`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PercentileRule {
+    pub block_target: u16,
+    /// between 0 and 100
+    pub percentile: f64,
+    /// how many of the last blocks are considered
+    pub blocks: usize,
+}
+
+/// Model-free estimator returning a percentile of the fee rates of the transactions confirmed in
+/// the last blocks
+#[derive(Debug, Clone)]
+pub struct PercentileEstimator {
+    rules: Vec<PercentileRule>,
+}
+
+impl PercentileEstimator {
+    /// the rule of the first entry having a block target greater or equal than the requested one
+    /// is used, or the last one if none is.
+    pub fn new(mut rules: Vec<PercentileRule>) -> Self {
+        rules.sort_by_key(|r| r.block_target);
+        PercentileEstimator { rules }
+    }
+
+    fn rule(&self, block_target: u16) -> Result<&PercentileRule, Error> {
+        self.rules
+            .iter()
+            .find(|r| r.block_target >= block_target)
+            .or_else(|| self.rules.last())
+            .ok_or(Error::MissingFeeRates)
+    }
+
+    /// estimate using the fee rates of every block in `fee_rates_by_block`, ordered from the
+    /// oldest to the newest one. Only the last `blocks` of the matching rule are considered
+    pub fn estimate_by_block(
+        &self,
+        block_target: u16,
+        fee_rates_by_block: &[Vec<f64>],
+    ) -> Result<f32, Error> {
+        let rule = self.rule(block_target)?;
+        let skip = fee_rates_by_block.len().saturating_sub(rule.blocks);
+        let fee_rates: Vec<f64> = fee_rates_by_block[skip..].concat();
+        percentile(&fee_rates, rule.percentile)
+            .map(|fee_rate| fee_rate as f32)
+            .ok_or(Error::MissingFeeRates)
+    }
+}
+
+impl Default for PercentileEstimator {
+    /// higher percentiles of fewer blocks for lower targets
+    fn default() -> Self {
+        let rule = |block_target, percentile, blocks| PercentileRule {
+            block_target,
+            percentile,
+            blocks,
+        };
+        PercentileEstimator::new(vec![
+            rule(1, 90.0, 1),
+            rule(2, 75.0, 2),
+            rule(6, 50.0, 3),
+            rule(144, 25.0, 6),
+            rule(1008, 10.0, 10),
+        ])
+    }
+}
+
+/// nearest-rank percentile of `values`, None if `values` is empty
+// `f64::clamp` is not available on the minimum supported rust version
+#[allow(clippy::manual_clamp)]
+pub fn percentile(values: &[f64], percentile: f64) -> Option<f64> {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let rank = (percentile.max(0.0).min(100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.max(1) - 1])
+}
+
+impl FeeEstimator for PercentileEstimator {
+    /// same as [`PercentileEstimator::estimate_by_block`]
+    fn estimate_fee(
+        &self,
+        block_target: u16,
+        _timestamp: Option<u32>,
+        blocks_data: Option<BlocksData>,
+    ) -> Result<f32, Error> {
+        let blocks_data = blocks_data.ok_or(Error::MissingFeeRates)?;
+        self.estimate_by_block(block_target, blocks_data.fee_rates_by_block)
+    }
+
+    fn name(&self) -> &str {
+        "percentile"
+    }
+}
+
+/// Tries every estimator in order until one succeeds
+#[derive(Default)]
+pub struct FallbackChain {
+    estimators: Vec<Box<dyn FeeEstimator>>,
+}
+
+impl FallbackChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<E: FeeEstimator + 'static>(mut self, estimator: E) -> Self {
+        self.estimators.push(Box::new(estimator));
+        self
+    }
+
+    /// returns the first successful estimate with the name of the estimator that produced it,
+    /// or the error of the last estimator if none succeeds
+    pub fn estimate_with_source(
+        &self,
+        block_target: u16,
+        timestamp: Option<u32>,
+        blocks_data: Option<BlocksData>,
+    ) -> Result<(f32, &str), Error> {
+        let mut last_err = Error::MissingFeeRates;
+        for estimator in self.estimators.iter() {
+            match estimator.estimate_fee(block_target, timestamp, blocks_data) {
+                Ok(fee_rate) => return Ok((fee_rate, estimator.name())),
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+}
+
+impl FeeEstimator for FallbackChain {
+    fn estimate_fee(
+        &self,
+        block_target: u16,
+        timestamp: Option<u32>,
+        blocks_data: Option<BlocksData>,
+    ) -> Result<f32, Error> {
+        self.estimate_with_source(block_target, timestamp, blocks_data)
+            .map(|(fee_rate, _)| fee_rate)
+    }
+
+    fn name(&self) -> &str {
+        "chain"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_model_high, get_model_low};
+
+    #[test]
+    fn test_percentile() {
+        let values = [5.0, 1.0, 4.0, 2.0, 3.0];
+        assert_eq!(percentile(&values, 0.0), Some(1.0));
+        assert_eq!(percentile(&values, 50.0), Some(3.0));
+        assert_eq!(percentile(&values, 90.0), Some(5.0));
+        assert_eq!(percentile(&values, 100.0), Some(5.0));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_static() {
+        let estimator = StaticEstimator::new(vec![(6, 5.0), (1, 20.0)]);
+        assert_eq!(estimator.estimate_fee(1, None, None).unwrap(), 20.0);
+        assert_eq!(estimator.estimate_fee(3, None, None).unwrap(), 5.0);
+        assert_eq!(estimator.estimate_fee(100, None, None).unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_percentile_estimator() {
+        let estimator = PercentileEstimator::default();
+        let by_block = vec![
+            vec![1.0; 10],
+            vec![2.0; 10],
+            (1..=10).map(|e| e as f64 * 10.0).collect::<Vec<_>>(),
+        ];
+
+        assert_eq!(estimator.estimate_by_block(1, &by_block).unwrap(), 90.0);
+        assert_eq!(estimator.estimate_by_block(2, &by_block).unwrap(), 50.0);
+        assert_eq!(estimator.estimate_by_block(3, &by_block).unwrap(), 2.0);
+        assert_eq!(estimator.estimate_by_block(1008, &by_block).unwrap(), 1.0);
+        assert_eq!(estimator.estimate_by_block(2000, &by_block).unwrap(), 1.0);
+        assert!(matches!(
+            estimator.estimate_by_block(1, &[vec![]]),
+            Err(Error::MissingFeeRates)
+        ));
+
+        // the rules are honored through the trait
+        for target in [1, 2, 3, 1008].iter() {
+            assert_eq!(
+                estimator
+                    .estimate_fee(*target, None, Some(BlocksData::new(&by_block, 0)))
+                    .unwrap(),
+                estimator.estimate_by_block(*target, &by_block).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_fallback_chain() {
+        let chain = FallbackChain::new()
+            .push(FeeModel::new(get_model_low(), get_model_high()))
+            .push(PercentileEstimator::default())
+            .push(StaticEstimator::new(vec![(1, 20.0)]));
+
+        let (fee_rate, source) = chain.estimate_with_source(1, None, None).unwrap();
+        assert_eq!((fee_rate, source), (20.0, "static"));
+
+        let by_block = [vec![10.0], vec![1.0, 2.0, 3.0]];
+        let (fee_rate, source) = chain
+            .estimate_with_source(
+                1,
+                Some(1613708045),
+                Some(BlocksData::new(&by_block, 1613707745)),
+            )
+            .unwrap();
+        assert_eq!(source, "model");
+        let model = FeeModel::new(get_model_low(), get_model_high());
+        let fee_rates = [10.0, 1.0, 2.0, 3.0];
+        assert_eq!(
+            fee_rate,
+            model
+                .estimate(1, Some(1613708045), &fee_rates, 1613707745)
+                .unwrap()
+        );
+
+        // only the last block for target 1
+        let chain = FallbackChain::new().push(PercentileEstimator::default());
+        let (fee_rate, source) = chain
+            .estimate_with_source(1, None, Some(BlocksData::new(&by_block, 0)))
+            .unwrap();
+        assert_eq!((fee_rate, source), (3.0, "percentile"));
+        assert!(matches!(
+            chain.estimate_fee(1, None, None),
+            Err(Error::MissingFeeRates)
+        ));
+    }
+}
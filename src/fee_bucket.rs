@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 #[derive(Debug)]
 pub struct FeeBuckets {
     buckets_limits: Vec<f64>,
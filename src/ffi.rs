@@ -0,0 +1,257 @@
+//! C API, the header `include/bitcoin_fee_model.h` is generated from this file by `build.rs`.
+//! Functions return a [`FeeModelError`] and write their results through out pointers.
+#![allow(unsafe_code)]
+
+use std::convert::TryInto;
+use std::slice;
+
+use bitcoin::consensus::deserialize_partial;
+use bitcoin::Block;
+
+use crate::{get_model_high, get_model_low, Error, Size64};
+
+/// Opaque handle to the bundled models
+pub type FeeModel = crate::FeeModel<Size64>;
+
+/// number of fee buckets written by `fee_model_process_blocks`
+pub const FEE_BUCKETS_LEN: usize = 16;
+
+/// Error codes, mirroring the variants of the Rust `Error`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeeModelError {
+    Ok = 0,
+    MissingMeanData = 1,
+    MissingStdData = 2,
+    UnconnectedBlocks = 3,
+    LastTsMissing = 4,
+    MissingQuantiles = 5,
+    QuantileOutOfRange = 6,
+    MissingFeeRates = 7,
+    InvalidModel = 8,
+    BlockSource = 9,
+    InvalidDataset = 10,
+    InvalidInput = 11,
+    MissingTimestamp = 12,
+    NullPointer = 13,
+}
+
+impl From<Error> for FeeModelError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::MissingMeanData(_) => FeeModelError::MissingMeanData,
+            Error::MissingStdData(_) => FeeModelError::MissingStdData,
+            Error::UnconnectedBlocks => FeeModelError::UnconnectedBlocks,
+            Error::LastTsMissing => FeeModelError::LastTsMissing,
+            Error::MissingQuantiles => FeeModelError::MissingQuantiles,
+            Error::QuantileOutOfRange(_) => FeeModelError::QuantileOutOfRange,
+            Error::MissingFeeRates => FeeModelError::MissingFeeRates,
+            Error::InvalidModel(_) => FeeModelError::InvalidModel,
+            Error::BlockSource(_) => FeeModelError::BlockSource,
+            Error::InvalidDataset(_) => FeeModelError::InvalidDataset,
+            Error::InvalidInput(_) => FeeModelError::InvalidInput,
+            Error::MissingTimestamp => FeeModelError::MissingTimestamp,
+        }
+    }
+}
+
+/// the slice at `ptr`, which may be null only if `len` is 0
+unsafe fn slice_or_null<'a, T>(ptr: *const T, len: usize) -> Result<&'a [T], FeeModelError> {
+    match (ptr.is_null(), len) {
+        (_, 0) => Ok(&[]),
+        (true, _) => Err(FeeModelError::NullPointer),
+        (false, _) => Ok(slice::from_raw_parts(ptr, len)),
+    }
+}
+
+fn result(r: Result<(), FeeModelError>) -> FeeModelError {
+    r.err().unwrap_or(FeeModelError::Ok)
+}
+
+/// Create the bundled models, to be released with `fee_model_free`
+#[no_mangle]
+pub extern "C" fn fee_model_new() -> *mut FeeModel {
+    Box::into_raw(Box::new(FeeModel::new(get_model_low(), get_model_high())))
+}
+
+/// Release the models created with `fee_model_new`, `model` may be null
+///
+/// # Safety
+/// `model` must be returned by `fee_model_new` and not already released
+#[no_mangle]
+pub unsafe extern "C" fn fee_model_free(model: *mut FeeModel) {
+    if !model.is_null() {
+        drop(Box::from_raw(model));
+    }
+}
+
+/// Write to `out` the fee rate in sat/vbytes to confirm within `block_target` given the
+/// `fee_buckets` written by `fee_model_process_blocks`
+///
+/// # Safety
+/// `model` must be a live handle, `fee_buckets` must point to `fee_buckets_len` values and `out`
+/// to a writable float
+#[no_mangle]
+pub unsafe extern "C" fn fee_model_estimate_with_buckets(
+    model: *const FeeModel,
+    block_target: u16,
+    timestamp: u32,
+    fee_buckets: *const u64,
+    fee_buckets_len: usize,
+    last_block_ts: u32,
+    out: *mut f32,
+) -> FeeModelError {
+    let estimate = || -> Result<(), FeeModelError> {
+        let model = model.as_ref().ok_or(FeeModelError::NullPointer)?;
+        let fee_buckets = slice_or_null(fee_buckets, fee_buckets_len)?;
+        let out = out.as_mut().ok_or(FeeModelError::NullPointer)?;
+        *out = model.estimate_with_buckets(
+            block_target,
+            Some(timestamp),
+            fee_buckets,
+            last_block_ts,
+        )?;
+        Ok(())
+    };
+    result(estimate())
+}
+
+/// Compute the fee buckets and the timestamp of the last block from the 10 last blocks, serialized
+/// one after the other from the oldest in `blocks`
+///
+/// # Safety
+/// `blocks` must point to `blocks_len` bytes, `fee_buckets` to `fee_buckets_len` writable values
+/// and `last_block_ts` to a writable integer
+#[no_mangle]
+pub unsafe extern "C" fn fee_model_process_blocks(
+    blocks: *const u8,
+    blocks_len: usize,
+    fee_buckets: *mut u64,
+    fee_buckets_len: usize,
+    last_block_ts: *mut u32,
+) -> FeeModelError {
+    let process = || -> Result<(), FeeModelError> {
+        let mut bytes = slice_or_null(blocks, blocks_len)?;
+        if fee_buckets.is_null() {
+            return Err(FeeModelError::NullPointer);
+        }
+        if fee_buckets_len < FEE_BUCKETS_LEN {
+            return Err(FeeModelError::InvalidInput);
+        }
+        let last_block_ts = last_block_ts.as_mut().ok_or(FeeModelError::NullPointer)?;
+
+        let mut parsed = Vec::with_capacity(10);
+        while !bytes.is_empty() {
+            let (block, consumed): (Block, _) =
+                deserialize_partial(bytes).map_err(|_| FeeModelError::InvalidInput)?;
+            parsed.push(block);
+            bytes = &bytes[consumed..];
+        }
+        let parsed: Box<[Block; 10]> = parsed
+            .into_boxed_slice()
+            .try_into()
+            .map_err(|_| FeeModelError::InvalidInput)?;
+
+        let (fee_rates, ts) = crate::process_blocks(&parsed)?;
+        let buckets = crate::fee_buckets(&fee_rates);
+        slice::from_raw_parts_mut(fee_buckets, FEE_BUCKETS_LEN).copy_from_slice(&buckets);
+        *last_block_ts = ts;
+        Ok(())
+    };
+    result(process())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_files::tests::{chain, spend};
+    use crate::model_data::tests::BUCKETS;
+    use bitcoin::consensus::serialize;
+    use std::ptr;
+
+    #[test]
+    fn test_estimate_with_buckets() {
+        let model = fee_model_new();
+        let ts = 1613708045u32;
+        let expected = unsafe { &*model }
+            .estimate_with_buckets(2, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        let mut out = 0.0f32;
+        unsafe {
+            let code = fee_model_estimate_with_buckets(
+                model,
+                2,
+                ts,
+                BUCKETS.as_ptr(),
+                BUCKETS.len(),
+                ts - 300,
+                &mut out,
+            );
+            assert_eq!(code, FeeModelError::Ok);
+            assert_eq!(out, expected);
+
+            let code = fee_model_estimate_with_buckets(
+                ptr::null(),
+                2,
+                ts,
+                BUCKETS.as_ptr(),
+                BUCKETS.len(),
+                ts - 300,
+                &mut out,
+            );
+            assert_eq!(code, FeeModelError::NullPointer);
+            let code =
+                fee_model_estimate_with_buckets(model, 2, ts, ptr::null(), 16, ts - 300, &mut out);
+            assert_eq!(code, FeeModelError::NullPointer);
+
+            fee_model_free(model);
+            fee_model_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_process_blocks() {
+        let mut blocks = chain(10);
+        let tx = spend(&blocks[0].txdata[0], 0, 1000);
+        blocks[5].txdata.push(tx);
+        for i in 6..10 {
+            blocks[i].header.prev_blockhash = blocks[i - 1].block_hash();
+        }
+        let bytes: Vec<u8> = blocks.iter().flat_map(serialize).collect();
+
+        let mut buckets = [0u64; FEE_BUCKETS_LEN];
+        let mut last_block_ts = 0u32;
+        let process = |bytes: &[u8], buckets: &mut [u64], last_block_ts: &mut u32| unsafe {
+            fee_model_process_blocks(
+                bytes.as_ptr(),
+                bytes.len(),
+                buckets.as_mut_ptr(),
+                buckets.len(),
+                last_block_ts,
+            )
+        };
+        let code = process(&bytes, &mut buckets, &mut last_block_ts);
+        assert_eq!(code, FeeModelError::Ok);
+        let (fee_rates, ts) = crate::process_blocks(&blocks.try_into().unwrap()).unwrap();
+        assert_eq!(buckets.to_vec(), crate::fee_buckets(&fee_rates));
+        assert_eq!(last_block_ts, ts);
+
+        let nine = &bytes[..bytes.len() - serialize(&chain(1)[0]).len()];
+        let code = process(nine, &mut buckets, &mut last_block_ts);
+        assert_eq!(code, FeeModelError::InvalidInput);
+        let code = process(&bytes[..bytes.len() - 1], &mut buckets, &mut last_block_ts);
+        assert_eq!(code, FeeModelError::InvalidInput);
+        let code = process(&bytes, &mut buckets[..15], &mut last_block_ts);
+        assert_eq!(code, FeeModelError::InvalidInput);
+    }
+
+    #[test]
+    fn test_header() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/bitcoin_fee_model.h"));
+        let committed = include_str!("../include/bitcoin_fee_model.h");
+        assert_eq!(
+            generated, committed,
+            "include/bitcoin_fee_model.h is outdated, copy it from the OUT_DIR"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+//! Minimal HTTP server for testing the clients of remote APIs
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+
+pub struct Request {
+    /// like `/block/<hash>`
+    #[cfg_attr(not(feature = "esplora"), allow(dead_code))]
+    pub path: String,
+    #[cfg_attr(not(feature = "rpc"), allow(dead_code))]
+    pub headers: String,
+    #[cfg_attr(not(feature = "rpc"), allow(dead_code))]
+    pub body: String,
+}
+
+/// serve every connection in a background thread with `handler` returning the status code and
+/// the body of the response. Returns the base url of the server
+pub fn serve<F, B>(handler: F) -> String
+where
+    F: Fn(&Request) -> (u16, B) + Send + 'static,
+    B: Into<Vec<u8>>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let mut headers = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                let lower = header.to_lowercase();
+                if let Some(len) = lower.strip_prefix("content-length:") {
+                    content_length = len.trim().parse().unwrap();
+                }
+                headers.push_str(&header);
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let request = Request {
+                path: line.split(' ').nth(1).unwrap_or("").to_string(),
+                headers,
+                body: String::from_utf8(body).unwrap(),
+            };
+
+            let (status, body) = handler(&request);
+            let body = body.into();
+            let head = format!(
+                "HTTP/1.1 {} Stub\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len(),
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    });
+    url
+}
@@ -1,18 +1,71 @@
+// the C API in the `ffi` module is the only place allowed to use unsafe
+#![cfg_attr(not(feature = "ffi"), forbid(unsafe_code))]
+#![cfg_attr(feature = "ffi", deny(unsafe_code))]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
+// the code generated by `include_fee_model!` refers to `::bitcoin_fee_model`
+extern crate self as bitcoin_fee_model;
+
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
 
-use chrono::{DateTime, Datelike, NaiveDateTime, Timelike, Utc};
+use alloc::boxed::Box;
+use chrono::{Datelike, NaiveDate, TimeZone, Timelike, Utc};
 
 use crate::fee_bucket::FeeBuckets;
 pub use crate::matrix::{size::*, SizeMarker};
-use crate::model_data::ModelData;
 
+pub mod clock;
+#[cfg(feature = "std")]
+pub mod compare;
+#[cfg(feature = "electrum")]
+pub mod electrum;
 mod error;
+#[cfg(feature = "esplora")]
+pub mod esplora;
+#[cfg(feature = "std")]
+pub mod estimator;
 mod fee_bucket;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(all(test, any(feature = "rpc", feature = "esplora")))]
+mod http_stub;
 mod matrix;
 mod model_data;
+#[cfg(feature = "onnx")]
+mod onnx;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+#[cfg(feature = "std")]
+pub mod smart_fee;
+#[cfg(feature = "train")]
+pub mod train;
 
+#[cfg(feature = "use-bitcoin")]
+pub mod backtest;
+#[cfg(feature = "use-bitcoin")]
+pub mod block_files;
+#[cfg(feature = "use-bitcoin")]
+pub mod block_source;
+#[cfg(feature = "use-bitcoin")]
+pub mod block_window;
+#[cfg(feature = "use-bitcoin")]
+pub mod bump;
+#[cfg(feature = "use-bitcoin")]
+pub mod dataset;
 #[cfg(feature = "use-bitcoin")]
 pub mod process_blocks;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "zmq-listener")]
+pub mod zmq_listener;
 
 #[cfg(feature = "use-bitcoin")]
 pub extern crate bitcoin;
@@ -20,61 +73,322 @@ pub extern crate bitcoin;
 #[cfg(feature = "use-bitcoin")]
 pub use process_blocks::process_blocks;
 
+#[cfg(feature = "macros")]
+pub use bitcoin_fee_model_macros::include_fee_model;
+#[cfg(feature = "std")]
+pub use clock::SystemClock;
+pub use clock::{Clock, Estimate, FixedClock, TargetEstimate};
 pub use error::Error;
+#[cfg(feature = "std")]
+pub use estimator::{FallbackChain, FeeEstimator};
 pub use model_data::models::*;
+pub use model_data::{FieldsDescribe, ModelData};
 
-pub struct FeeModel<N> {
-    /// for 1,2 blocks
-    low: ModelData<Size20, N, Size1>,
-    /// for 3-1008 blocks
-    high: ModelData<Size20, N, Size1>,
+/// used by the code generated by the `include_fee_model!` macro, not part of the public api
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::model_data::{fields_describe, matrix_from_le_bytes, strings, Weights};
 }
 
-impl<N: SizeMarker> FeeModel<N> {
-    pub fn new(low: ModelData<Size20, N, Size1>, high: ModelData<Size20, N, Size1>) -> FeeModel<N> {
-        FeeModel { low, high }
+/// the maximum `block_target` supported by the models
+pub const MAX_BLOCK_TARGET: u16 = 1008;
+
+/// minimum relay fee rate in sat/vbytes, same default as Bitcoin Core
+pub const MIN_RELAY_FEE: f64 = 1.0;
+
+/// names of the features returned by [`features`], in the order of the bundled models
+pub const FEATURES: [&str; 20] = [
+    "confirms_in",
+    "b0",
+    "b1",
+    "b2",
+    "b3",
+    "b4",
+    "b5",
+    "b6",
+    "b7",
+    "b8",
+    "b9",
+    "b10",
+    "b11",
+    "b12",
+    "b13",
+    "b14",
+    "b15",
+    "delta_last",
+    "day_of_week",
+    "hour",
+];
+
+/// names of the additional time features returned by [`Features::get`], models request them by
+/// listing them in their fields. Cyclic ones are encoded as `sin` and `cos` so that the end of a
+/// period is close to its start
+pub const TIME_FEATURES: [&str; 10] = [
+    "minute_of_day",
+    "minute_of_day_sin",
+    "minute_of_day_cos",
+    "hour_sin",
+    "hour_cos",
+    "day_of_week_sin",
+    "day_of_week_cos",
+    "day_of_month",
+    "days_to_month_end",
+    "month",
+];
+
+/// the fee buckets of `fee_rates`, as used in the model features `b0`..`b15`
+pub fn fee_buckets(fee_rates: &[f64]) -> Vec<u64> {
+    FeeBuckets::new(50, 500.0).get(fee_rates)
+}
+
+/// The model input features, this is the only place where they are computed, both for estimating
+/// and for building datasets (see the `dataset` module) so that models are trained on the same features.
+/// Without the std feature, callers without a clock can build it with explicit time components
+#[derive(Debug, Clone, PartialEq)]
+pub struct Features {
+    pub confirms_in: u16,
+    /// the fee buckets as returned by [`fee_buckets`], only the first 16 are used
+    pub fee_buckets: Vec<u64>,
+    /// seconds elapsed since the last block
+    pub delta_last: i64,
+    /// from 0 for monday to 6 for sunday
+    pub day_of_week: u32,
+    pub hour: u32,
+    /// minutes since midnight, from 0 to 1439
+    pub minute_of_day: u32,
+    /// from 1
+    pub day_of_month: u32,
+    /// from 28 to 31, used for `days_to_month_end`
+    pub days_in_month: u32,
+    /// from 1 for january to 12 for december
+    pub month: u32,
+}
+
+impl Features {
+    /// the features at `timestamp` of a transaction confirming in `block_target`, the time
+    /// components are in UTC
+    pub fn new(block_target: u16, timestamp: u32, fee_buckets: &[u64], last_block_ts: u32) -> Self {
+        Self::with_utc_offset(block_target, timestamp, fee_buckets, last_block_ts, 0)
     }
 
-    pub fn estimate_with_buckets(
-        &self,
+    /// same as [`Features::new`] with the time components in the timezone `utc_offset` seconds
+    /// east of UTC, see [`ModelData::utc_offset`]
+    pub fn with_utc_offset(
         block_target: u16,
-        timestamp: Option<u32>,
+        timestamp: u32,
         fee_buckets: &[u64],
         last_block_ts: u32,
-    ) -> Result<f32, Error> {
-        let mut input = HashMap::new();
-        input.insert("confirms_in".to_string(), block_target as f32);
+        utc_offset: i32,
+    ) -> Self {
+        let local = Utc
+            .timestamp_opt(timestamp as i64 + utc_offset as i64, 0)
+            .unwrap();
+        let (year, month) = match local.month() {
+            12 => (local.year() + 1, 1),
+            month => (local.year(), month + 1),
+        };
+        let days_in_month = NaiveDate::from_ymd_opt(year, month, 1)
+            .and_then(|first| first.pred_opt())
+            .map(|last| last.day())
+            .unwrap_or(31);
+        Features {
+            confirms_in: block_target,
+            fee_buckets: fee_buckets.to_vec(),
+            delta_last: timestamp as i64 - last_block_ts as i64,
+            day_of_week: local.weekday().num_days_from_monday(),
+            hour: local.hour(),
+            minute_of_day: local.hour() * 60 + local.minute(),
+            day_of_month: local.day(),
+            days_in_month,
+            month: local.month(),
+        }
+    }
 
-        let utc: DateTime<Utc> = match timestamp {
-            Some(timestamp) => {
-                let naive = NaiveDateTime::from_timestamp(timestamp as i64, 0);
-                DateTime::from_utc(naive, Utc)
+    /// the value of the feature `name`, one of [`FEATURES`] or [`TIME_FEATURES`]
+    pub fn get(&self, name: &str) -> Option<f32> {
+        match name {
+            "confirms_in" => Some(self.confirms_in as f32),
+            "delta_last" => Some(self.delta_last as f32),
+            "day_of_week" => Some(self.day_of_week as f32),
+            "hour" => Some(self.hour as f32),
+            "minute_of_day" => Some(self.minute_of_day as f32),
+            "minute_of_day_sin" => Some(libm::sinf(angle(self.minute_of_day, 1440))),
+            "minute_of_day_cos" => Some(libm::cosf(angle(self.minute_of_day, 1440))),
+            "hour_sin" => Some(libm::sinf(angle(self.hour, 24))),
+            "hour_cos" => Some(libm::cosf(angle(self.hour, 24))),
+            "day_of_week_sin" => Some(libm::sinf(angle(self.day_of_week, 7))),
+            "day_of_week_cos" => Some(libm::cosf(angle(self.day_of_week, 7))),
+            "day_of_month" => Some(self.day_of_month as f32),
+            "days_to_month_end" => {
+                Some(self.days_in_month.saturating_sub(self.day_of_month) as f32)
             }
-            None => Utc::now(),
-        };
-        let day_of_week = utc.weekday().num_days_from_monday() as f32;
-        input.insert("day_of_week".to_string(), day_of_week);
-        input.insert("hour".to_string(), utc.hour() as f32);
+            "month" => Some(self.month as f32),
+            _ => {
+                let i: usize = name.strip_prefix('b')?.parse().ok()?;
+                if i < 16 {
+                    self.fee_buckets.get(i).map(|b| *b as f32)
+                } else {
+                    None
+                }
+            }
+        }
+    }
 
-        let delta = utc.timestamp() - last_block_ts as i64;
-        input.insert("delta_last".to_string(), delta as f32);
+    /// the features by name, both [`FEATURES`] and [`TIME_FEATURES`]
+    #[cfg(feature = "std")]
+    pub fn to_map(&self) -> HashMap<String, f32> {
+        FEATURES
+            .iter()
+            .chain(TIME_FEATURES.iter())
+            .filter_map(|name| Some((name.to_string(), self.get(name)?)))
+            .collect()
+    }
+}
 
-        for (i, fee_bucket) in fee_buckets.iter().take(16).enumerate() {
-            input.insert(format!("b{}", i), *fee_bucket as f32);
-        }
+/// the position of `value` in a cycle of `period`, in radians
+fn angle(value: u32, period: u32) -> f32 {
+    2.0 * core::f32::consts::PI * value as f32 / period as f32
+}
+
+/// the model input features by name, see [`Features`].
+/// `timestamp` if None it's initialized to current time
+#[cfg(feature = "std")]
+pub fn features(
+    block_target: u16,
+    timestamp: Option<u32>,
+    fee_buckets: &[u64],
+    last_block_ts: u32,
+) -> HashMap<String, f32> {
+    let timestamp = timestamp.unwrap_or_else(|| SystemClock.now());
+    Features::new(block_target, timestamp, fee_buckets, last_block_ts).to_map()
+}
+
+pub struct FeeModel<N, O = Size1> {
+    /// for 1,2 blocks
+    low: ModelData<Size20, N, O>,
+    /// for 3-1008 blocks
+    high: ModelData<Size20, N, O>,
+    /// used when the timestamp is not given
+    clock: Option<Box<dyn Clock>>,
+}
+
+impl<N: SizeMarker, O: SizeMarker> FeeModel<N, O> {
+    /// the models using the [`SystemClock`], or no clock without the std feature
+    pub fn new(low: ModelData<Size20, N, O>, high: ModelData<Size20, N, O>) -> FeeModel<N, O> {
+        #[cfg(feature = "std")]
+        let clock: Option<Box<dyn Clock>> = Some(Box::new(SystemClock));
+        #[cfg(not(feature = "std"))]
+        let clock = None;
+        FeeModel { low, high, clock }
+    }
+
+    /// use `clock` when the timestamp is not given
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Some(Box::new(clock));
+        self
+    }
 
+    /// require the timestamp to be given, returning [`Error::MissingTimestamp`] otherwise
+    pub fn without_clock(mut self) -> Self {
+        self.clock = None;
+        self
+    }
+
+    /// `timestamp` or the current time of the clock if None
+    fn timestamp(&self, timestamp: Option<u32>) -> Result<u32, Error> {
+        timestamp
+            .or_else(|| self.clock.as_ref().map(|clock| clock.now()))
+            .ok_or(Error::MissingTimestamp)
+    }
+
+    fn model(&self, block_target: u16) -> &ModelData<Size20, N, O> {
         if block_target <= 2 {
-            self.low.norm_predict(&input)
+            &self.low
         } else {
-            self.high.norm_predict(&input)
+            &self.high
         }
     }
 
+    /// the features for the model used for `block_target`, in its timezone
+    fn features(
+        &self,
+        block_target: u16,
+        timestamp: u32,
+        fee_buckets: &[u64],
+        last_block_ts: u32,
+    ) -> Features {
+        let utc_offset = self.model(block_target).utc_offset;
+        Features::with_utc_offset(
+            block_target,
+            timestamp,
+            fee_buckets,
+            last_block_ts,
+            utc_offset,
+        )
+    }
+
+    /// compute the fee estimation from already extracted `features`, the model is chosen
+    /// according to the `confirms_in` feature
+    #[cfg(feature = "std")]
+    pub fn estimate_features(&self, features: &HashMap<String, f32>) -> Result<f32, Error> {
+        let block_target = features.get("confirms_in").copied().unwrap_or(0.0);
+        self.model(block_target as u16).norm_predict(features)
+    }
+
+    /// same as [`FeeModel::estimate_features`], available without the std feature. The time
+    /// components of `features` must be in the timezone of the model, see [`ModelData::utc_offset`]
+    pub fn estimate_with_features(&self, features: &Features) -> Result<f32, Error> {
+        let model = self.model(features.confirms_in);
+        let input = model.norm_with(|field| features.get(field))?;
+        Ok(model.predict(&input))
+    }
+
+    pub fn estimate_with_buckets(
+        &self,
+        block_target: u16,
+        timestamp: Option<u32>,
+        fee_buckets: &[u64],
+        last_block_ts: u32,
+    ) -> Result<f32, Error> {
+        self.estimate_timestamped_with_buckets(block_target, timestamp, fee_buckets, last_block_ts)
+            .map(|estimate| estimate.fee_rate)
+    }
+
+    /// same as [`FeeModel::estimate_with_buckets`], returning also the timestamp used
+    pub fn estimate_timestamped_with_buckets(
+        &self,
+        block_target: u16,
+        timestamp: Option<u32>,
+        fee_buckets: &[u64],
+        last_block_ts: u32,
+    ) -> Result<Estimate, Error> {
+        let timestamp = self.timestamp(timestamp)?;
+        let features = self.features(block_target, timestamp, fee_buckets, last_block_ts);
+        Ok(Estimate {
+            fee_rate: self.estimate_with_features(&features)?,
+            timestamp,
+        })
+    }
+
+    /// same as [`FeeModel::estimate`], returning also the timestamp used so that the estimate
+    /// can be replayed
+    pub fn estimate_timestamped(
+        &self,
+        block_target: u16,
+        timestamp: Option<u32>,
+        fee_rates: &[f64],
+        last_block_ts: u32,
+    ) -> Result<Estimate, Error> {
+        let fee_buckets = fee_buckets(fee_rates);
+        self.estimate_timestamped_with_buckets(block_target, timestamp, &fee_buckets, last_block_ts)
+    }
+
     /// compute the fee estimation given the desired `block_target`
-    /// `timestamp` if None it's initialized to current time.
+    /// `timestamp` if None it's initialized to the current time of the model clock.
     /// `fee_rates` contains the fee rates of transactions in the last 10 blocks, only for transactions
     /// having inputs in this last 10 blocks (so the fee rate is known)
-    /// `last_block_ts` last
+    /// `last_block_ts` last.
+    /// With quantile models this is the lowest quantile, see [`FeeModel::estimate_quantile`]
     pub fn estimate(
         &self,
         block_target: u16,
@@ -82,18 +396,181 @@ impl<N: SizeMarker> FeeModel<N> {
         fee_rates: &[f64],
         last_block_ts: u32,
     ) -> Result<f32, Error> {
-        let fee_buckets = FeeBuckets::new(50, 500.0).get(fee_rates);
+        let fee_buckets = fee_buckets(fee_rates);
         self.estimate_with_buckets(block_target, timestamp, &fee_buckets, last_block_ts)
     }
+
+    /// returns the lowest block target for which the estimated fee rate is lower or equal than
+    /// `fee_rate`, or `None` if a higher fee rate is needed even for [`MAX_BLOCK_TARGET`].
+    /// Other parameters are the same as [`FeeModel::estimate_with_buckets`]
+    pub fn target_for_fee_rate_with_buckets(
+        &self,
+        fee_rate: f32,
+        timestamp: Option<u32>,
+        fee_buckets: &[u64],
+        last_block_ts: u32,
+    ) -> Result<Option<u16>, Error> {
+        self.target_for_fee_rate_timestamped_with_buckets(
+            fee_rate,
+            timestamp,
+            fee_buckets,
+            last_block_ts,
+        )
+        .map(|target| target.block_target)
+    }
+
+    /// same as [`FeeModel::target_for_fee_rate_with_buckets`], returning also the timestamp used
+    pub fn target_for_fee_rate_timestamped_with_buckets(
+        &self,
+        fee_rate: f32,
+        timestamp: Option<u32>,
+        fee_buckets: &[u64],
+        last_block_ts: u32,
+    ) -> Result<TargetEstimate, Error> {
+        let timestamp = self.timestamp(timestamp)?;
+        // the models may have a different `utc_offset`
+        let low_features = self.features(1, timestamp, fee_buckets, last_block_ts);
+        let high_features = self.features(3, timestamp, fee_buckets, last_block_ts);
+        let mut low = self.low.norm_with(|field| low_features.get(field))?;
+        let mut high = self.high.norm_with(|field| high_features.get(field))?;
+
+        for block_target in 1..=MAX_BLOCK_TARGET {
+            let (model, input) = if block_target <= 2 {
+                (&self.low, &mut low)
+            } else {
+                (&self.high, &mut high)
+            };
+            model.norm_update(input, "confirms_in", block_target as f32)?;
+            if model.predict(input) <= fee_rate {
+                return Ok(TargetEstimate {
+                    block_target: Some(block_target),
+                    timestamp,
+                });
+            }
+        }
+
+        Ok(TargetEstimate {
+            block_target: None,
+            timestamp,
+        })
+    }
+
+    /// returns the expected number of blocks for a transaction paying `fee_rate` to confirm,
+    /// see [`FeeModel::target_for_fee_rate_with_buckets`]
+    pub fn target_for_fee_rate(
+        &self,
+        fee_rate: f32,
+        timestamp: Option<u32>,
+        fee_rates: &[f64],
+        last_block_ts: u32,
+    ) -> Result<Option<u16>, Error> {
+        let fee_buckets = fee_buckets(fee_rates);
+        self.target_for_fee_rate_with_buckets(fee_rate, timestamp, &fee_buckets, last_block_ts)
+    }
+
+    /// same as [`FeeModel::target_for_fee_rate`], returning also the timestamp used
+    pub fn target_for_fee_rate_timestamped(
+        &self,
+        fee_rate: f32,
+        timestamp: Option<u32>,
+        fee_rates: &[f64],
+        last_block_ts: u32,
+    ) -> Result<TargetEstimate, Error> {
+        let fee_buckets = fee_buckets(fee_rates);
+        self.target_for_fee_rate_timestamped_with_buckets(
+            fee_rate,
+            timestamp,
+            &fee_buckets,
+            last_block_ts,
+        )
+    }
+
+    pub fn estimate_quantile_with_buckets(
+        &self,
+        block_target: u16,
+        probability: f32,
+        timestamp: Option<u32>,
+        fee_buckets: &[u64],
+        last_block_ts: u32,
+    ) -> Result<f32, Error> {
+        self.estimate_quantile_timestamped_with_buckets(
+            block_target,
+            probability,
+            timestamp,
+            fee_buckets,
+            last_block_ts,
+        )
+        .map(|estimate| estimate.fee_rate)
+    }
+
+    /// same as [`FeeModel::estimate_quantile_with_buckets`], returning also the timestamp used
+    pub fn estimate_quantile_timestamped_with_buckets(
+        &self,
+        block_target: u16,
+        probability: f32,
+        timestamp: Option<u32>,
+        fee_buckets: &[u64],
+        last_block_ts: u32,
+    ) -> Result<Estimate, Error> {
+        let timestamp = self.timestamp(timestamp)?;
+        let features = self.features(block_target, timestamp, fee_buckets, last_block_ts);
+        let model = self.model(block_target);
+        let input = model.norm_with(|field| features.get(field))?;
+        Ok(Estimate {
+            fee_rate: model.predict_quantile(&input, probability)?,
+            timestamp,
+        })
+    }
+
+    /// compute the fee rate having the given `probability` of confirming within `block_target`,
+    /// interpolating between the quantiles output by the models.
+    /// Other parameters are the same as [`FeeModel::estimate`]
+    pub fn estimate_quantile(
+        &self,
+        block_target: u16,
+        probability: f32,
+        timestamp: Option<u32>,
+        fee_rates: &[f64],
+        last_block_ts: u32,
+    ) -> Result<f32, Error> {
+        let fee_buckets = fee_buckets(fee_rates);
+        self.estimate_quantile_with_buckets(
+            block_target,
+            probability,
+            timestamp,
+            &fee_buckets,
+            last_block_ts,
+        )
+    }
+
+    /// same as [`FeeModel::estimate_quantile`], returning also the timestamp used
+    pub fn estimate_quantile_timestamped(
+        &self,
+        block_target: u16,
+        probability: f32,
+        timestamp: Option<u32>,
+        fee_rates: &[f64],
+        last_block_ts: u32,
+    ) -> Result<Estimate, Error> {
+        let fee_buckets = fee_buckets(fee_rates);
+        self.estimate_quantile_timestamped_with_buckets(
+            block_target,
+            probability,
+            timestamp,
+            &fee_buckets,
+            last_block_ts,
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::model_data::tests::BUCKETS;
-    use crate::model_data::ModelData;
     use crate::*;
     use crate::{get_model_high, get_model_low};
+    #[cfg(feature = "std")]
     use serde::Deserialize;
+    #[cfg(feature = "std")]
     use std::collections::HashMap;
 
     const EPS_1000: f32 = f32::EPSILON * 1000.0;
@@ -121,6 +598,154 @@ mod tests {
         assert!(one > two, "1 block ({}) > 2 ({})", one, two);
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    pub fn test_features() {
+        let ts = 1613708045u32; // friday 2021-02-19 04:14:05 UTC
+        let features = Features::new(6, ts, &BUCKETS, ts - 300);
+        assert_eq!(features.get("confirms_in"), Some(6.0));
+        assert_eq!(features.get("b0"), Some(13.0));
+        assert_eq!(features.get("b15"), Some(58.0));
+        assert_eq!(features.get("b16"), None);
+        assert_eq!(features.get("delta_last"), Some(300.0));
+        assert_eq!(features.get("day_of_week"), Some(4.0));
+        assert_eq!(features.get("hour"), Some(4.0));
+        assert_eq!(features.get("unknown"), None);
+        assert_eq!(
+            features.to_map(),
+            crate::features(6, Some(ts), &BUCKETS, ts - 300)
+        );
+
+        let model = FeeModel::new(get_model_low(), get_model_high());
+        let with_map = model.estimate_features(&features.to_map()).unwrap();
+        assert_eq!(model.estimate_with_features(&features).unwrap(), with_map);
+        let values: Vec<f32> = model
+            .high
+            .fields
+            .iter()
+            .map(|f| features.get(f).unwrap())
+            .collect();
+        let input = model.high.norm_values(&values).unwrap();
+        assert_eq!(model.high.predict(&input), with_map);
+        assert!(matches!(
+            model.high.norm_values(&values[1..]),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_time_features() {
+        let ts = 1613708045u32; // friday 2021-02-19 04:14:05 UTC
+        let features = Features::new(6, ts, &BUCKETS, ts - 300);
+        assert_eq!(features.get("minute_of_day"), Some(254.0));
+        assert_eq!(features.get("day_of_month"), Some(19.0));
+        assert_eq!(features.get("days_to_month_end"), Some(9.0));
+        assert_eq!(features.get("month"), Some(2.0));
+        let angle = |value: f32, period: f32| 2.0 * core::f32::consts::PI * value / period;
+        let get = |name| features.get(name).unwrap();
+        assert_approx_eq(get("minute_of_day_sin"), libm::sinf(angle(254.0, 1440.0)));
+        assert_approx_eq(get("minute_of_day_cos"), libm::cosf(angle(254.0, 1440.0)));
+        assert_approx_eq(get("hour_sin"), libm::sinf(angle(4.0, 24.0)));
+        assert_approx_eq(get("hour_cos"), libm::cosf(angle(4.0, 24.0)));
+        assert_approx_eq(get("day_of_week_sin"), libm::sinf(angle(4.0, 7.0)));
+        assert_approx_eq(get("day_of_week_cos"), libm::cosf(angle(4.0, 7.0)));
+        #[cfg(feature = "std")]
+        assert_eq!(
+            features.to_map().len(),
+            FEATURES.len() + TIME_FEATURES.len()
+        );
+
+        // 2021-02-18 23:14:05 in New York
+        let eastern = Features::with_utc_offset(6, ts, &BUCKETS, ts - 300, -5 * 3600);
+        assert_eq!(eastern.day_of_week, 3);
+        assert_eq!(eastern.hour, 23);
+        assert_eq!(eastern.get("minute_of_day"), Some(23.0 * 60.0 + 14.0));
+        assert_eq!(eastern.get("day_of_month"), Some(18.0));
+        assert_eq!(eastern.get("delta_last"), Some(300.0));
+
+        // 2020-12-31 23:00:00 UTC is new year in Paris
+        let paris = Features::with_utc_offset(6, 1609455600, &BUCKETS, 1609455000, 3600);
+        assert_eq!(
+            (paris.day_of_month, paris.days_in_month, paris.month),
+            (1, 31, 1)
+        );
+        assert_eq!(paris.get("days_to_month_end"), Some(30.0));
+
+        // 2020-02-29 12:00:00 UTC
+        let leap = Features::new(6, 1582977600, &BUCKETS, 1582977000);
+        assert_eq!(leap.days_in_month, 29);
+        assert_eq!(leap.get("days_to_month_end"), Some(0.0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    pub fn test_time_features_model() {
+        // the test model with the last two fields replaced by time features
+        let model = |utc_offset| {
+            let mut model = get_model_test_model();
+            model.fields[18] = "day_of_week_cos".to_string();
+            model.fields[19] = "minute_of_day_sin".to_string();
+            let describe = |value| model.fields.iter().map(move |f| (f.clone(), value));
+            model.norm = FieldsDescribe::new(describe(0.0), describe(1.0));
+            model.utc_offset = utc_offset;
+            model
+        };
+        let ts = 1613708045u32;
+        let offset = -5 * 3600;
+        let eastern = FeeModel::new(model(offset), model(offset));
+        let features = Features::with_utc_offset(6, ts, &BUCKETS, ts - 300, offset);
+        let expected = eastern.high.norm_predict(&features.to_map()).unwrap();
+        let estimate = eastern
+            .estimate_with_buckets(6, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        assert_eq!(estimate, expected);
+
+        let utc = FeeModel::new(model(0), model(0));
+        assert_ne!(
+            utc.estimate_with_buckets(6, Some(ts), &BUCKETS, ts - 300)
+                .unwrap(),
+            estimate
+        );
+    }
+
+    #[test]
+    pub fn test_target_for_fee_rate() {
+        let model = FeeModel::new(get_model_low(), get_model_high());
+        let ts = 1613708045u32;
+        let target = model
+            .target_for_fee_rate_with_buckets(f32::MAX, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        assert_eq!(target, Some(1));
+
+        for block_target in [1u16, 2, 6, 24].iter() {
+            let fee_rate = model
+                .estimate_with_buckets(*block_target, Some(ts), &BUCKETS, ts - 300)
+                .unwrap();
+            let target = model
+                .target_for_fee_rate_with_buckets(fee_rate, Some(ts), &BUCKETS, ts - 300)
+                .unwrap()
+                .unwrap();
+            assert!(target <= *block_target, "{} > {}", target, block_target);
+        }
+    }
+
+    #[test]
+    pub fn test_estimate_quantile() {
+        let model = FeeModel::new(
+            get_model_test_quantile_model(),
+            get_model_test_quantile_model(),
+        );
+        let ts = 1613708045u32;
+        let low = model
+            .estimate_quantile_with_buckets(6, 0.3, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        let high = model
+            .estimate_quantile_with_buckets(6, 0.7, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        assert!(low < high, "30% ({}) < 70% ({})", low, high);
+    }
+
+    #[cfg(feature = "std")]
     #[derive(Deserialize)]
     struct TestVector {
         test_vector: Vec<f32>,
@@ -129,6 +754,7 @@ mod tests {
 
     /// ensure the models are loaded correctly by using a test_vector created at the end of the training
     /// for example ensure models are not swapped between high and low or parameters are swapped
+    #[cfg(feature = "std")]
     #[test]
     pub fn test_vector() {
         // ensure the model is loaded correct
@@ -141,8 +767,9 @@ mod tests {
         test_single_vector(&model.high, bytes_high);
     }
 
+    #[cfg(feature = "std")]
     fn test_single_vector(model: &ModelData<Size20, Size64, Size1>, bytes: &[u8]) {
-        let test: TestVector = serde_cbor::from_slice(&bytes[..]).unwrap();
+        let test: TestVector = serde_cbor::from_slice(bytes).unwrap();
 
         let mut input = HashMap::new();
         for (i, field) in model.fields.iter().enumerate() {
@@ -0,0 +1,145 @@
+use bitcoin_fee_model::block_files::{last_connected, read_block_file, read_last_blocks};
+use bitcoin_fee_model::{
+    get_model_high, get_model_low, process_blocks, FeeModel, MAX_BLOCK_TARGET,
+};
+use std::convert::TryInto;
+use std::error::Error;
+
+const USAGE: &str = "Usage: bitcoin-fee-model [--json] [--targets <t1,t2,...>] [--timestamp <ts>] \
+(--blocks-dir <dir> | <block file>...)
+
+Estimate fee rates in sat/vbytes from the last 10 blocks, given as files containing one raw or hex
+encoded block each, or as a Bitcoin Core blocks directory containing blk*.dat files";
+
+struct Args {
+    json: bool,
+    targets: Vec<u16>,
+    timestamp: Option<u32>,
+    blocks_dir: Option<String>,
+    block_files: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut args = Args {
+        json: false,
+        targets: vec![1, 2, 3, 6, 12, 24, 144, 1008],
+        timestamp: None,
+        blocks_dir: None,
+        block_files: vec![],
+    };
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| format!("missing value of {}", arg))
+        };
+        match arg.as_str() {
+            "--json" => args.json = true,
+            "--targets" => args.targets = parse_targets(&value()?)?,
+            "--timestamp" => args.timestamp = Some(value()?.parse()?),
+            "--blocks-dir" => args.blocks_dir = Some(value()?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg).into()),
+            _ => args.block_files.push(arg),
+        }
+    }
+    Ok(args)
+}
+
+/// the comma separated block targets in `value`, between 1 and `MAX_BLOCK_TARGET`
+fn parse_targets(value: &str) -> Result<Vec<u16>, Box<dyn Error>> {
+    let mut targets = vec![];
+    for target in value.split(',') {
+        let target: u16 = target
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid target {:?}", target))?;
+        if target == 0 || target > MAX_BLOCK_TARGET {
+            return Err(format!(
+                "target {} must be between 1 and {}",
+                target, MAX_BLOCK_TARGET
+            )
+            .into());
+        }
+        targets.push(target);
+    }
+    Ok(targets)
+}
+
+/// the estimates as JSON, non finite fee rates are `null`
+fn to_json(last_block_ts: u32, estimates: &[(u16, f32)]) -> String {
+    let estimates: Vec<String> = estimates
+        .iter()
+        .map(|(target, fee_rate)| match fee_rate {
+            f if f.is_finite() => format!("\"{}\":{:.2}", target, f),
+            _ => format!("\"{}\":null", target),
+        })
+        .collect();
+    format!(
+        "{{\"last_block_ts\":{},\"estimates\":{{{}}}}}",
+        last_block_ts,
+        estimates.join(",")
+    )
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = parse_args()?;
+    let blocks = match &args.blocks_dir {
+        Some(dir) => read_last_blocks(dir, 10)?,
+        None => args
+            .block_files
+            .iter()
+            .map(read_block_file)
+            .collect::<Result<_, _>>()?,
+    };
+    if blocks.is_empty() {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    }
+    let blocks = last_connected(blocks, 10).ok_or("10 connected blocks are required")?;
+    let blocks: &[_; 10] = blocks[..].try_into()?;
+    let (fee_rates, last_block_ts) = process_blocks(blocks)?;
+
+    let model = FeeModel::new(get_model_low(), get_model_high());
+    let mut estimates = vec![];
+    for target in args.targets.iter() {
+        let fee_rate = model.estimate(*target, args.timestamp, &fee_rates, last_block_ts)?;
+        estimates.push((*target, fee_rate));
+    }
+
+    if args.json {
+        println!("{}", to_json(last_block_ts, &estimates));
+    } else {
+        println!("target  fee rate");
+        for (target, fee_rate) in estimates {
+            println!("{:>6} {:>9.2}", target, fee_rate);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_targets, to_json};
+
+    #[test]
+    fn test_parse_targets() {
+        assert_eq!(parse_targets("1, 6,1008").unwrap(), vec![1, 6, 1008]);
+        assert!(parse_targets("0").is_err());
+        assert!(parse_targets("1009").is_err());
+        assert!(parse_targets("1,x").is_err());
+        assert!(parse_targets("").is_err());
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(
+            to_json(10, &[(1, 12.345), (2, f32::NAN), (3, f32::INFINITY)]),
+            "{\"last_block_ts\":10,\"estimates\":{\"1\":12.35,\"2\":null,\"3\":null}}"
+        );
+    }
+}
@@ -1,5 +1,6 @@
-use std::marker::PhantomData;
-use std::ops::{Index, IndexMut};
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::ops::{Index, IndexMut};
 
 pub mod size {
     include!(concat!(env!("OUT_DIR"), "/sizes.rs"));
@@ -7,7 +8,7 @@ pub mod size {
 
 use size::*;
 
-pub trait SizeMarker: std::fmt::Debug {
+pub trait SizeMarker: core::fmt::Debug {
     fn size() -> usize;
 }
 
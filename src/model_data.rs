@@ -1,3 +1,7 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
 
 use crate::matrix::{size::*, Matrix, SizeMarker};
@@ -5,17 +9,50 @@ use crate::Error;
 
 pub mod models {
     use super::*;
-    use crate::matrix::Matrix;
 
     include!(concat!(env!("OUT_DIR"), "/models.rs"));
 }
 
+/// decode the little-endian `f32` in `bytes`, used by the models embedded at build time
+pub(crate) fn from_le_bytes(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+/// the matrix of the little-endian `f32` in `bytes`, used by the models embedded at build time
+/// and by the `include_fee_model!` macro
+pub fn matrix_from_le_bytes<W: SizeMarker, H: SizeMarker>(bytes: &[u8]) -> Matrix<W, H> {
+    Matrix::from_buffer(from_le_bytes(bytes).into_boxed_slice())
+}
+
+/// the `fields` names, used by the embedded models
+pub fn strings(fields: &[&str]) -> Vec<String> {
+    fields.iter().map(|f| f.to_string()).collect()
+}
+
+/// the normalization of the fields from `(field, value)` pairs, used by the embedded models
+pub fn fields_describe(mean: &[(&str, f32)], std: &[(&str, f32)]) -> FieldsDescribe {
+    let map = |v: &[(&str, f32)]| {
+        v.iter()
+            .map(|(k, v)| (k.to_string(), *v))
+            .collect::<Vec<_>>()
+    };
+    FieldsDescribe::new(map(mean), map(std))
+}
+
 #[derive(Debug)]
 pub struct ModelData<I, N, O> {
     pub norm: FieldsDescribe,
     pub weights: Weights<I, O, N, N>,
     pub fields: Vec<String>,
     pub alpha: f32,
+    /// the probabilities represented by each output, empty for single output models
+    pub quantiles: Vec<f32>,
+    /// seconds east of UTC of the timezone of the time features, like `hour` or `day_of_month`,
+    /// the model has been trained with
+    pub utc_offset: i32,
 }
 
 #[derive(Debug)]
@@ -30,14 +67,30 @@ pub struct Weights<I, O, N1, N2> {
     pub l2_kernel: Matrix<O, N2>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FieldsDescribe {
-    mean: HashMap<String, f32>,
-    std: HashMap<String, f32>,
+    pub(crate) mean: BTreeMap<String, f32>,
+    pub(crate) std: BTreeMap<String, f32>,
+}
+
+impl FieldsDescribe {
+    /// the `mean` and the `std` of every field, used to normalize the model input
+    pub fn new<M: IntoIterator<Item = (String, f32)>>(mean: M, std: M) -> Self {
+        FieldsDescribe {
+            mean: mean.into_iter().collect(),
+            std: std.into_iter().collect(),
+        }
+    }
 }
 
 impl<I: SizeMarker, N: SizeMarker, O: SizeMarker> ModelData<I, N, O> {
+    /// the first output of the model, for quantile models it's the lowest quantile, use
+    /// [`ModelData::predict_quantile`] for the others
     pub fn predict(&self, input: &Matrix<I, Size1>) -> f32 {
+        self.predict_outputs(input)[0][0]
+    }
+
+    pub fn predict_outputs(&self, input: &Matrix<I, Size1>) -> Matrix<O, Size1> {
         let a1 = input.dot(&self.weights.l0_kernel);
         let a2 = a1.add(&self.weights.l0_bias);
         let a3 = a2.relu(self.alpha);
@@ -47,49 +100,204 @@ impl<I: SizeMarker, N: SizeMarker, O: SizeMarker> ModelData<I, N, O> {
         let b3 = b2.relu(self.alpha);
 
         let c1 = b3.dot(&self.weights.l2_kernel);
-        let c2 = c1.add(&self.weights.l2_bias);
-
-        c2[0][0]
+        c1.add(&self.weights.l2_bias)
     }
 
+    #[cfg(feature = "std")]
     pub fn norm(&self, input: &HashMap<String, f32>) -> Result<Matrix<I, Size1>, Error> {
+        self.norm_with(|field| input.get(field).copied())
+    }
+
+    /// normalize the input `values`, given in the same order of [`ModelData::fields`]
+    pub fn norm_values(&self, values: &[f32]) -> Result<Matrix<I, Size1>, Error> {
+        if values.len() != self.fields.len() {
+            return Err(Error::InvalidInput(format!(
+                "expected {} values, found {}",
+                self.fields.len(),
+                values.len()
+            )));
+        }
+        let mut values = values.iter();
+        self.norm_with(|_| values.next().copied())
+    }
+
+    /// normalize the input, `get` returns the value of every field in the order of
+    /// [`ModelData::fields`], missing ones are 0
+    pub(crate) fn norm_with<F: FnMut(&str) -> Option<f32>>(
+        &self,
+        mut get: F,
+    ) -> Result<Matrix<I, Size1>, Error> {
         let mut result = vec![];
         for field in self.fields.iter() {
-            let x = input.get(field).unwrap_or(&0.0);
-            let std = self
-                .norm
-                .std
-                .get(field)
-                .ok_or_else(|| Error::MissingStdData(field.clone()))?;
-            let mean = self
-                .norm
-                .mean
-                .get(field)
-                .ok_or_else(|| Error::MissingMeanData(field.clone()))?;
-            let res = (x - mean) / std;
-            result.push(res)
+            let x = get(field).unwrap_or(0.0);
+            result.push(self.norm_value(field, x)?)
         }
         Ok(Matrix::from_array(result.into_boxed_slice()))
     }
 
+    fn norm_value(&self, field: &str, x: f32) -> Result<f32, Error> {
+        let std = self
+            .norm
+            .std
+            .get(field)
+            .ok_or_else(|| Error::MissingStdData(field.to_string()))?;
+        let mean = self
+            .norm
+            .mean
+            .get(field)
+            .ok_or_else(|| Error::MissingMeanData(field.to_string()))?;
+        Ok((x - mean) / std)
+    }
+
+    /// update the single `field` of an already normalized `input`, nothing is done if the model
+    /// doesn't use `field`
+    pub fn norm_update(
+        &self,
+        input: &mut Matrix<I, Size1>,
+        field: &str,
+        x: f32,
+    ) -> Result<(), Error> {
+        if let Some(i) = self.fields.iter().position(|f| f == field) {
+            input[0][i] = self.norm_value(field, x)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
     pub fn norm_predict(&self, input: &HashMap<String, f32>) -> Result<f32, Error> {
         let input = self.norm(input)?;
         Ok(self.predict(&input))
     }
+
+    /// predict the value at the given `probability` by linearly interpolating between the two
+    /// nearest quantiles output by the model
+    #[cfg(feature = "std")]
+    pub fn norm_predict_quantile(
+        &self,
+        input: &HashMap<String, f32>,
+        probability: f32,
+    ) -> Result<f32, Error> {
+        let input = self.norm(input)?;
+        self.predict_quantile(&input, probability)
+    }
+
+    /// same as [`ModelData::norm_predict_quantile`] with an already normalized `input`
+    pub fn predict_quantile(
+        &self,
+        input: &Matrix<I, Size1>,
+        probability: f32,
+    ) -> Result<f32, Error> {
+        if self.quantiles.is_empty() {
+            return Err(Error::MissingQuantiles);
+        }
+        let first = self.quantiles[0];
+        let last = self.quantiles[self.quantiles.len() - 1];
+        if !(first..=last).contains(&probability) {
+            return Err(Error::QuantileOutOfRange(probability));
+        }
+
+        let outputs = &self.predict_outputs(input)[0];
+
+        let upper = self
+            .quantiles
+            .iter()
+            .position(|q| *q >= probability)
+            .unwrap_or(self.quantiles.len() - 1);
+        if upper == 0 {
+            return Ok(outputs[0]);
+        }
+        let lower = upper - 1;
+        let (q0, q1) = (self.quantiles[lower], self.quantiles[upper]);
+        let t = (probability - q0) / (q1 - q0);
+        Ok(outputs[lower] + t * (outputs[upper] - outputs[lower]))
+    }
+}
+
+/// the matrix named `name` of a loaded model, checking its size
+#[cfg(any(feature = "cbor", feature = "onnx"))]
+pub(crate) fn matrix<W: SizeMarker, H: SizeMarker>(
+    name: &str,
+    v: Vec<f32>,
+) -> Result<Matrix<W, H>, Error> {
+    if v.len() != W::size() * H::size() {
+        return Err(Error::InvalidModel(format!(
+            "{} expected size {}, found {}",
+            name,
+            W::size() * H::size(),
+            v.len()
+        )));
+    }
+    Ok(Matrix::from_buffer(v.into_boxed_slice()))
+}
+
+#[cfg(feature = "cbor")]
+impl<I: SizeMarker, N: SizeMarker, O: SizeMarker> ModelData<I, N, O> {
+    /// load a model from the content of a `model.cbor` file, checking its sizes match the types
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, Error> {
+        let raw: bitcoin_fee_model_codegen::RawModelData =
+            serde_cbor::from_slice(bytes).map_err(|e| Error::InvalidModel(e.to_string()))?;
+        Self::from_raw(raw)
+    }
+}
+
+#[cfg(any(feature = "cbor", feature = "onnx"))]
+impl<I: SizeMarker, N: SizeMarker, O: SizeMarker> ModelData<I, N, O> {
+    /// the model of `raw`, with the same checks done on the models embedded at build time
+    pub(crate) fn from_raw(raw: bitcoin_fee_model_codegen::RawModelData) -> Result<Self, Error> {
+        raw.validate().map_err(Error::InvalidModel)?;
+        if raw.fields.len() != I::size() {
+            return Err(Error::InvalidModel(format!(
+                "expected {} fields, found {}",
+                I::size(),
+                raw.fields.len()
+            )));
+        }
+        let w = raw.weights;
+
+        Ok(ModelData {
+            norm: FieldsDescribe {
+                mean: raw.norm.mean.into_iter().collect(),
+                std: raw.norm.std.into_iter().collect(),
+            },
+            weights: Weights {
+                l0_bias: matrix("l0_bias", w.l0_bias)?,
+                l0_kernel: matrix("l0_kernel", w.l0_kernel.concat())?,
+                l1_bias: matrix("l1_bias", w.l1_bias)?,
+                l1_kernel: matrix("l1_kernel", w.l1_kernel.concat())?,
+                l2_bias: matrix("l2_bias", w.l2_bias)?,
+                l2_kernel: matrix("l2_kernel", w.l2_kernel.concat())?,
+            },
+            fields: raw.fields,
+            alpha: raw.alpha,
+            quantiles: raw.quantiles,
+            utc_offset: raw.utc_offset,
+        })
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::excessive_precision)]
 pub mod tests {
+    #[cfg(feature = "std")]
     use std::collections::HashMap;
 
     use crate::matrix::{size::*, Matrix};
     use crate::tests::assert_approx_eq;
+    #[cfg(feature = "std")]
+    use crate::Error;
     use crate::ModelData;
 
     pub fn get_test_model() -> ModelData<Size20, Size4, Size1> {
         crate::get_model_test_model()
     }
 
+    /// same as the test model, with the outputs scaled by 0.8, 1.0 and 1.2 for the quantiles
+    /// 0.25, 0.5 and 0.75
+    #[cfg(feature = "std")]
+    pub fn get_test_quantile_model() -> ModelData<Size20, Size4, Size3> {
+        crate::get_model_test_quantile_model()
+    }
+
     #[rustfmt::skip]
     pub fn get_test_input() -> Matrix<Size20, Size1> {
         Matrix::from_array(vec![-0.23901028,  0.02662498, -0.19410163,  0.03187769, -0.2026636,  -0.31123071,
@@ -102,6 +310,7 @@ pub mod tests {
         13u64, 1, 32, 24, 14, 62, 1174, 453, 197, 291, 333, 3304, 307, 229, 36, 58,
     ];
 
+    #[cfg(feature = "std")]
     pub fn get_test_pre_norm() -> HashMap<String, f32> {
         let mut map = HashMap::new();
         map.insert("confirms_in".to_string(), 11.0);
@@ -125,6 +334,19 @@ pub mod tests {
         assert_approx_eq(model.predict(&input), get_test_result())
     }
 
+    /// doesn't touch the file system and doesn't use `Utc::now`, so that it runs under Miri
+    #[test]
+    fn test_from_le_bytes() {
+        let bytes = [0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x00, 0xc0, 0xff];
+        assert_eq!(super::from_le_bytes(&bytes), vec![1.0, -2.0]);
+
+        #[cfg(feature = "std")]
+        assert_approx_eq(
+            get_test_model().norm_predict(&get_test_pre_norm()).unwrap(),
+            get_test_result(),
+        );
+    }
+
     #[test]
     fn test_vector() {
         let model = get_test_model();
@@ -156,6 +378,96 @@ pub mod tests {
         assert_approx_eq(c2[0][0], get_test_result())
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_norm_update() {
+        let model = get_test_model();
+        let mut input = get_test_pre_norm();
+        let mut norm = model.norm(&input).unwrap();
+
+        input.insert("confirms_in".to_string(), 3.0);
+        model.norm_update(&mut norm, "confirms_in", 3.0).unwrap();
+        model.norm_update(&mut norm, "not_a_field", 3.0).unwrap();
+        norm.assert_approx_eq(&model.norm(&input).unwrap());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_predict_quantile() {
+        let model = get_test_quantile_model();
+        let input = get_test_pre_norm();
+        let expected = get_test_result();
+
+        let median = model.norm_predict_quantile(&input, 0.5).unwrap();
+        assert_approx_eq(median, expected);
+        assert_approx_eq(model.norm_predict(&input).unwrap(), expected * 0.8);
+
+        let lowest = model.norm_predict_quantile(&input, 0.25).unwrap();
+        assert!((lowest - expected * 0.8).abs() < 1e-4);
+        let interpolated = model.norm_predict_quantile(&input, 0.625).unwrap();
+        assert!((interpolated - expected * 1.1).abs() < 1e-4);
+
+        assert!(matches!(
+            model.norm_predict_quantile(&input, 0.95),
+            Err(Error::QuantileOutOfRange(_))
+        ));
+        assert!(matches!(
+            get_test_model().norm_predict_quantile(&input, 0.5),
+            Err(Error::MissingQuantiles)
+        ));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_from_cbor() {
+        use bitcoin_fee_model_codegen::RawModelData;
+
+        let bytes = include_bytes!("../models/test_model.cbor");
+        let model = ModelData::<Size20, Size4, Size1>::from_cbor(bytes).unwrap();
+        let input = get_test_input();
+        assert_approx_eq(model.predict(&input), get_test_result());
+
+        let err = ModelData::<Size20, Size64, Size1>::from_cbor(bytes).unwrap_err();
+        assert!(matches!(err, Error::InvalidModel(_)));
+
+        // same checks of the models embedded at build time
+        let with = |f: &dyn Fn(&mut RawModelData)| {
+            let mut raw: RawModelData = serde_cbor::from_slice(bytes).unwrap();
+            f(&mut raw);
+            let bytes = serde_cbor::to_vec(&raw).unwrap();
+            match ModelData::<Size20, Size4, Size1>::from_cbor(&bytes) {
+                Err(Error::InvalidModel(e)) => e,
+                r => panic!("{:?}", r.map(|_| ())),
+            }
+        };
+        assert_eq!(
+            with(&|m| m.quantiles = vec![0.25, 0.5, 0.75]),
+            "Layer 2 must have one output per quantile, or a single one without quantiles. Found: 1, 3"
+        );
+        assert_eq!(
+            with(&|m| m.quantiles = vec![0.5, 0.4]),
+            "Quantiles must be strictly increasing. Found: [0.5, 0.4]"
+        );
+        // transposed, with the same number of weights
+        assert_eq!(
+            with(&|m| {
+                let kernel = m.weights.l0_kernel.concat();
+                m.weights.l0_kernel = kernel.chunks(20).map(<[f32]>::to_vec).collect();
+            }),
+            "Layer 0 kernel must be 20x4. Found: 4x{20}"
+        );
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn test_include_fee_model() {
+        let model: ModelData<Size20, Size4, Size1> =
+            crate::include_fee_model!("models/test_model.cbor");
+        assert_approx_eq(model.predict(&get_test_input()), get_test_result());
+        assert_eq!(model.fields, get_test_model().fields);
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     #[rustfmt::skip]
     fn test_norm() {
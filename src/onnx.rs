@@ -0,0 +1,520 @@
+//! Import of ONNX models made of dense layers, reading the protobuf encoding without dependencies
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use crate::matrix::SizeMarker;
+use crate::model_data::FieldsDescribe;
+use crate::{Error, ModelData};
+use bitcoin_fee_model_codegen::{RawFieldsDescribe, RawModelData, RawWeights};
+
+fn invalid(msg: &str) -> Error {
+    Error::InvalidModel(format!("ONNX {}", msg))
+}
+
+/// A field of a protobuf message
+enum Value<'a> {
+    Varint(u64),
+    Fixed64,
+    Bytes(&'a [u8]),
+    Fixed32([u8; 4]),
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos.checked_add(len).filter(|e| *e <= self.bytes.len());
+        let end = end.ok_or_else(|| invalid("truncated message"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("varint too long"))
+    }
+
+    /// the next field number and value, None at the end of the message
+    fn next(&mut self) -> Result<Option<(u64, Value<'a>)>, Error> {
+        if self.pos == self.bytes.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed64
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => Value::Fixed32(self.take(4)?.try_into().unwrap()),
+            _ => return Err(invalid("unsupported wire type")),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+fn string(bytes: &[u8]) -> Result<String, Error> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| invalid("invalid string"))
+}
+
+/// floats of a repeated field, packed or not
+fn floats(value: Value, out: &mut Vec<f32>) -> Result<(), Error> {
+    match value {
+        Value::Fixed32(bytes) => out.push(f32::from_le_bytes(bytes)),
+        Value::Bytes(bytes) => {
+            if bytes.len() % 4 != 0 {
+                return Err(invalid("invalid float data"));
+            }
+            out.extend(
+                bytes
+                    .chunks(4)
+                    .map(|c| f32::from_le_bytes(c.try_into().unwrap())),
+            );
+        }
+        _ => return Err(invalid("invalid float data")),
+    }
+    Ok(())
+}
+
+/// a tensor dimension, encoded as int64
+fn dim(value: u64) -> Result<usize, Error> {
+    if (value as i64) < 0 {
+        return Err(invalid("negative tensor dimension"));
+    }
+    value.try_into().map_err(|_| invalid("tensor size"))
+}
+
+#[derive(Debug, Default)]
+struct Tensor {
+    name: String,
+    dims: Vec<usize>,
+    data: Vec<f32>,
+}
+
+impl Tensor {
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        const FLOAT: u64 = 1;
+        let mut tensor = Tensor::default();
+        let mut reader = Reader::new(bytes);
+        while let Some((field, value)) = reader.next()? {
+            match (field, value) {
+                (1, Value::Varint(value)) => tensor.dims.push(dim(value)?),
+                (1, Value::Bytes(packed)) => {
+                    let mut dims = Reader::new(packed);
+                    while dims.pos < packed.len() {
+                        tensor.dims.push(dim(dims.varint()?)?);
+                    }
+                }
+                (2, Value::Varint(data_type)) if data_type != FLOAT => {
+                    return Err(invalid(&format!("unsupported tensor type {}", data_type)))
+                }
+                (4, value) => floats(value, &mut tensor.data)?,
+                (8, Value::Bytes(name)) => tensor.name = string(name)?,
+                (9, Value::Bytes(raw)) => floats(Value::Bytes(raw), &mut tensor.data)?,
+                _ => (),
+            }
+        }
+        let size = tensor
+            .dims
+            .iter()
+            .try_fold(1usize, |size, dim| size.checked_mul(*dim))
+            .ok_or_else(|| invalid("tensor size"))?;
+        if size != tensor.data.len() {
+            return Err(invalid(&format!("tensor {} size mismatch", tensor.name)));
+        }
+        Ok(tensor)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    op_type: String,
+    float_attributes: HashMap<String, f32>,
+    int_attributes: HashMap<String, i64>,
+}
+
+impl Node {
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let mut node = Node::default();
+        let mut reader = Reader::new(bytes);
+        while let Some((field, value)) = reader.next()? {
+            match (field, value) {
+                (1, Value::Bytes(input)) => node.inputs.push(string(input)?),
+                (2, Value::Bytes(output)) => node.outputs.push(string(output)?),
+                (4, Value::Bytes(op_type)) => node.op_type = string(op_type)?,
+                (5, Value::Bytes(attribute)) => {
+                    let mut name = String::new();
+                    let mut reader = Reader::new(attribute);
+                    while let Some((field, value)) = reader.next()? {
+                        match (field, value) {
+                            (1, Value::Bytes(n)) => name = string(n)?,
+                            (2, Value::Fixed32(f)) => {
+                                node.float_attributes
+                                    .insert(name.clone(), f32::from_le_bytes(f));
+                            }
+                            (3, Value::Varint(i)) => {
+                                node.int_attributes.insert(name.clone(), i as i64);
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(node)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    initializers: HashMap<String, Tensor>,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+impl Graph {
+    /// the graph of the `ModelProto` in `bytes`
+    fn parse_model(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        while let Some((field, value)) = reader.next()? {
+            if let (7, Value::Bytes(graph)) = (field, value) {
+                return Graph::parse(graph);
+            }
+        }
+        Err(invalid("model without graph"))
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let value_info_name = |bytes: &[u8]| -> Result<String, Error> {
+            let mut reader = Reader::new(bytes);
+            while let Some((field, value)) = reader.next()? {
+                if let (1, Value::Bytes(name)) = (field, value) {
+                    return string(name);
+                }
+            }
+            Err(invalid("value info without name"))
+        };
+        let mut graph = Graph::default();
+        let mut reader = Reader::new(bytes);
+        while let Some((field, value)) = reader.next()? {
+            match (field, value) {
+                (1, Value::Bytes(node)) => graph.nodes.push(Node::parse(node)?),
+                (5, Value::Bytes(tensor)) => {
+                    let tensor = Tensor::parse(tensor)?;
+                    graph.initializers.insert(tensor.name.clone(), tensor);
+                }
+                (11, Value::Bytes(input)) => graph.inputs.push(value_info_name(input)?),
+                (12, Value::Bytes(output)) => graph.outputs.push(value_info_name(output)?),
+                _ => (),
+            }
+        }
+        Ok(graph)
+    }
+
+    fn initializer(&self, name: Option<&String>) -> Result<&Tensor, Error> {
+        let name = name.ok_or_else(|| invalid("missing node input"))?;
+        self.initializers
+            .get(name)
+            .ok_or_else(|| invalid(&format!("{} is not an initializer", name)))
+    }
+}
+
+/// A dense layer with the kernel stored by input
+struct Dense {
+    inputs: usize,
+    outputs: usize,
+    kernel: Vec<f32>,
+    bias: Vec<f32>,
+}
+
+/// the kernel of `dense` as rows of weights, one for every input
+fn rows(dense: &Dense) -> Vec<Vec<f32>> {
+    dense
+        .kernel
+        .chunks(dense.outputs.max(1))
+        .map(|row| row.to_vec())
+        .collect()
+}
+
+enum Layer {
+    Dense(Dense),
+    Activation(f32),
+}
+
+/// the layers of `graph`, which must be a chain of nodes starting from its input
+fn layers(graph: &Graph) -> Result<Vec<Layer>, Error> {
+    let mut current = graph
+        .inputs
+        .iter()
+        .find(|i| !graph.initializers.contains_key(*i))
+        .ok_or_else(|| invalid("graph without input"))?
+        .clone();
+    let mut layers = vec![];
+    for node in graph.nodes.iter() {
+        if node.inputs.first() != Some(&current) {
+            return Err(invalid(&format!(
+                "{} node is not applied to the output of the previous one",
+                node.op_type
+            )));
+        }
+        match node.op_type.as_str() {
+            "Gemm" => {
+                if node.int_attributes.get("transA").cloned().unwrap_or(0) != 0 {
+                    return Err(invalid("Gemm with transA is not supported"));
+                }
+                let trans_b = node.int_attributes.get("transB").cloned().unwrap_or(0) != 0;
+                let alpha = node.float_attributes.get("alpha").cloned().unwrap_or(1.0);
+                let beta = node.float_attributes.get("beta").cloned().unwrap_or(1.0);
+                let b = graph.initializer(node.inputs.get(1))?;
+                let mut dense = dense(b, trans_b)?;
+                dense.kernel.iter_mut().for_each(|w| *w *= alpha);
+                if node.inputs.len() > 2 {
+                    let c = graph.initializer(node.inputs.get(2))?;
+                    dense.bias = bias(c, dense.outputs)?;
+                    dense.bias.iter_mut().for_each(|b| *b *= beta);
+                }
+                layers.push(Layer::Dense(dense));
+            }
+            "MatMul" => {
+                let b = graph.initializer(node.inputs.get(1))?;
+                layers.push(Layer::Dense(dense(b, false)?));
+            }
+            "Add" => match layers.last_mut() {
+                Some(Layer::Dense(dense)) => {
+                    let c = graph.initializer(node.inputs.get(1))?;
+                    let add = bias(c, dense.outputs)?;
+                    dense.bias.iter_mut().zip(add).for_each(|(b, a)| *b += a);
+                }
+                _ => return Err(invalid("Add supported only after MatMul or Gemm")),
+            },
+            "Relu" => layers.push(Layer::Activation(0.0)),
+            "LeakyRelu" => {
+                let alpha = node.float_attributes.get("alpha").cloned().unwrap_or(0.01);
+                layers.push(Layer::Activation(alpha));
+            }
+            op => return Err(invalid(&format!("op {} is not supported", op))),
+        }
+        current = node
+            .outputs
+            .first()
+            .ok_or_else(|| invalid("node without output"))?
+            .clone();
+    }
+    if graph.outputs.first() != Some(&current) {
+        return Err(invalid("graph output is not the output of the last node"));
+    }
+    Ok(layers)
+}
+
+/// a dense layer without bias from the weight `b` of shape `[inputs, outputs]`, or
+/// `[outputs, inputs]` if `transposed`
+fn dense(b: &Tensor, transposed: bool) -> Result<Dense, Error> {
+    let (rows, cols) = match b.dims[..] {
+        [rows, cols] => (rows, cols),
+        _ => {
+            return Err(invalid(&format!(
+                "weight {} must have 2 dimensions",
+                b.name
+            )))
+        }
+    };
+    let (inputs, outputs) = if transposed {
+        (cols, rows)
+    } else {
+        (rows, cols)
+    };
+    let kernel = if transposed {
+        (0..inputs * outputs)
+            .map(|k| b.data[(k % outputs) * inputs + k / outputs])
+            .collect()
+    } else {
+        b.data.clone()
+    };
+    Ok(Dense {
+        inputs,
+        outputs,
+        kernel,
+        bias: vec![0.0; outputs],
+    })
+}
+
+fn bias(c: &Tensor, outputs: usize) -> Result<Vec<f32>, Error> {
+    if c.data.len() != outputs {
+        return Err(invalid(&format!(
+            "bias {} must have {} values",
+            c.name, outputs
+        )));
+    }
+    Ok(c.data.clone())
+}
+
+impl<I: SizeMarker, N: SizeMarker, O: SizeMarker> ModelData<I, N, O> {
+    /// load a model from the content of an ONNX file with a graph made of 3 dense layers, as
+    /// `Gemm` or `MatMul` followed by `Add`, the first two followed by the same `Relu` or
+    /// `LeakyRelu` activation. The ONNX graph doesn't contain the other model data, which must
+    /// be given: the `fields` names in the order of the graph input, their normalization and the
+    /// `quantiles` of the outputs, empty for single output models. The time features are in UTC,
+    /// set [`ModelData::utc_offset`] otherwise
+    pub fn from_onnx(
+        bytes: &[u8],
+        fields: Vec<String>,
+        norm: FieldsDescribe,
+        quantiles: Vec<f32>,
+    ) -> Result<Self, Error> {
+        let graph = Graph::parse_model(bytes)?;
+        let layers: Result<[Layer; 5], _> = layers(&graph)?.try_into();
+        let (l0, alpha, l1, alpha1, l2) = match layers {
+            Ok(
+                [Layer::Dense(l0), Layer::Activation(a0), Layer::Dense(l1), Layer::Activation(a1), Layer::Dense(l2)],
+            ) => (l0, a0, l1, a1, l2),
+            _ => {
+                return Err(invalid(
+                    "graph must be dense, activation, dense, activation, dense",
+                ))
+            }
+        };
+        if alpha != alpha1 {
+            return Err(invalid("activations must be the same"));
+        }
+        if l0.inputs != fields.len() || l1.inputs != l0.outputs || l2.inputs != l1.outputs {
+            return Err(invalid("layers sizes don't match"));
+        }
+
+        ModelData::from_raw(RawModelData {
+            norm: RawFieldsDescribe {
+                mean: norm.mean,
+                std: norm.std,
+            },
+            weights: RawWeights {
+                l0_kernel: rows(&l0),
+                l0_bias: l0.bias,
+                l1_kernel: rows(&l1),
+                l1_bias: l1.bias,
+                l2_kernel: rows(&l2),
+                l2_bias: l2.bias,
+            },
+            fields,
+            alpha,
+            quantiles,
+            utc_offset: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tensor;
+    use crate::matrix::size::*;
+    use crate::model_data::tests::{get_test_model, get_test_pre_norm, get_test_result};
+    use crate::tests::assert_approx_eq;
+    use crate::{Error, ModelData};
+
+    /// `test_model.cbor` exported as a `Gemm` with transposed weight, `LeakyRelu`, `MatMul` and
+    /// `Add`, `LeakyRelu` and `Gemm`
+    const TEST_MODEL: &[u8] = include_bytes!("../models/test_model.onnx");
+
+    fn from_onnx(bytes: &[u8]) -> Result<ModelData<Size20, Size4, Size1>, Error> {
+        let model = get_test_model();
+        ModelData::from_onnx(bytes, model.fields, model.norm, vec![])
+    }
+
+    fn replace(from: &[u8], to: &[u8]) -> Vec<u8> {
+        let mut bytes = TEST_MODEL.to_vec();
+        let pos = bytes.windows(from.len()).position(|w| w == from).unwrap();
+        bytes[pos..pos + from.len()].copy_from_slice(to);
+        bytes
+    }
+
+    #[test]
+    fn test_from_onnx() {
+        let model = from_onnx(TEST_MODEL).unwrap();
+        let result = model.norm_predict(&get_test_pre_norm()).unwrap();
+        assert_approx_eq(result, get_test_result());
+        assert_eq!(model.alpha, get_test_model().alpha);
+    }
+
+    #[test]
+    fn test_tensor_dims() {
+        // two dimensions of 2^33, overflowing the size
+        let big = [0x08, 0x80, 0x80, 0x80, 0x80, 0x20];
+        let err = Tensor::parse(&[big, big].concat()).unwrap_err();
+        assert!(err.to_string().contains("tensor size"), "{}", err);
+
+        let mut negative = vec![0x08];
+        negative.extend(&[0xff; 9]);
+        negative.push(0x01);
+        let err = Tensor::parse(&negative).unwrap_err();
+        assert!(err.to_string().contains("negative"), "{}", err);
+    }
+
+    #[test]
+    fn test_from_onnx_errors() {
+        let err = from_onnx(&replace(b"LeakyRelu", b"HardSwish")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid model: ONNX op HardSwish is not supported "
+        );
+
+        // the first activation with a different alpha
+        let alpha = 0.01f32.to_le_bytes();
+        let err = from_onnx(&replace(&alpha, &0.2f32.to_le_bytes())).unwrap_err();
+        assert!(err.to_string().contains("activations"), "{}", err);
+
+        // a wrong number of neurons
+        let model = get_test_model();
+        let result: Result<ModelData<Size20, Size8, Size1>, _> =
+            ModelData::from_onnx(TEST_MODEL, model.fields, model.norm, vec![]);
+        assert!(matches!(result, Err(Error::InvalidModel(_))));
+
+        // the same checks of the cbor models on the quantiles
+        let model = get_test_model();
+        let err = ModelData::<Size20, Size4, Size1>::from_onnx(
+            TEST_MODEL,
+            model.fields,
+            model.norm,
+            vec![0.1, 0.5, 0.9],
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("one output per quantile"),
+            "{}",
+            err
+        );
+        let model = get_test_model();
+        let err = ModelData::<Size20, Size4, Size1>::from_onnx(
+            TEST_MODEL,
+            model.fields,
+            model.norm,
+            vec![1.5],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("strictly increasing"), "{}", err);
+
+        assert!(from_onnx(&TEST_MODEL[..100]).is_err());
+        assert!(from_onnx(b"").is_err());
+    }
+}
@@ -17,6 +17,16 @@ pub fn process_blocks(blocks: &[bitcoin::Block; 10]) -> Result<(Vec<f64>, u32),
     Ok((fee_rates, last_block_ts))
 }
 
+/// same as [`process_blocks`] with the fee rates of every block, from the oldest
+pub fn process_blocks_by_block(
+    blocks: &[bitcoin::Block; 10],
+) -> Result<(Vec<Vec<f64>>, u32), Error> {
+    let txs = Transactions::from_blocks(blocks)?;
+    let fee_rates = txs.fee_rates_by_block(blocks);
+    let last_block_ts = txs.last_block_ts();
+    Ok((fee_rates, last_block_ts))
+}
+
 impl Transactions {
     pub fn from_blocks(blocks: &[Block; 10]) -> Result<Self, Error> {
         let mut prev = blocks[0].header.block_hash();
@@ -37,10 +47,7 @@ impl Transactions {
             }
         }
 
-        Ok(Self::from_txs(
-            txs,
-            time.ok_or_else(|| Error::LastTsMissing)?,
-        ))
+        Ok(Self::from_txs(txs, time.ok_or(Error::LastTsMissing)?))
     }
     pub fn from_txs(txs: HashMap<Txid, Transaction>, last_block_ts: u32) -> Self {
         let mut txs_output_values: HashMap<Txid, OutputValues> = HashMap::new();
@@ -66,6 +73,20 @@ impl Transactions {
         self.txs.keys().filter_map(|tx| self.fee_rate(tx)).collect()
     }
 
+    /// fee rates of the transactions in each of `blocks` having a known fee rate
+    pub fn fee_rates_by_block(&self, blocks: &[Block]) -> Vec<Vec<f64>> {
+        blocks
+            .iter()
+            .map(|block| {
+                block
+                    .txdata
+                    .iter()
+                    .filter_map(|tx| self.fee_rate(&tx.txid()))
+                    .collect()
+            })
+            .collect()
+    }
+
     fn absolute_fee(&self, tx: &Transaction) -> Option<u64> {
         let sum_outputs: u64 = tx.output.iter().map(|o| o.value).sum();
         let mut sum_inputs: u64 = 0;
@@ -83,7 +104,8 @@ impl Transactions {
 
 #[cfg(test)]
 mod tests {
-    use super::process_blocks;
+    use super::{process_blocks, process_blocks_by_block, Transactions};
+    use crate::block_files::tests::{chain, spend};
     use crate::Error;
     use bitcoin::blockdata::constants::genesis_block;
     use bitcoin::{Block, Network};
@@ -110,5 +132,41 @@ mod tests {
         let tx = blocks[0].txdata[0].clone();
         blocks[0].txdata.push(tx);
         process_blocks(&blocks).unwrap();
+
+        let txs = Transactions::from_blocks(&blocks).unwrap();
+        let by_block = txs.fee_rates_by_block(&blocks);
+        assert_eq!(by_block.len(), 10);
+        assert!(by_block.iter().all(|rates| rates.is_empty()));
+    }
+
+    #[test]
+    fn test_fee_rates_by_block() {
+        let mut blocks = chain(10);
+        let coinbase = blocks[0].txdata[0].clone();
+        let mut funding = spend(&coinbase, 0, 0);
+        let mut output = funding.output[0].clone();
+        output.value /= 4;
+        funding.output = vec![output; 4];
+        blocks[1].txdata.push(funding.clone());
+        blocks[4].txdata.push(spend(&funding, 0, 1000));
+        blocks[7].txdata.push(spend(&funding, 1, 2000));
+        blocks[7].txdata.push(spend(&funding, 2, 3000));
+        for i in 1..10 {
+            blocks[i].header.prev_blockhash = blocks[i - 1].block_hash();
+        }
+        let blocks: [Block; 10] = blocks.try_into().unwrap();
+
+        let vsize = spend(&funding, 0, 1000).get_weight() as f64 / 4.0;
+        let (by_block, last_block_ts) = process_blocks_by_block(&blocks).unwrap();
+        let mut expected = vec![vec![]; 10];
+        expected[1] = vec![0.0];
+        expected[4] = vec![1000.0 / vsize];
+        expected[7] = vec![2000.0 / vsize, 3000.0 / vsize];
+        assert_eq!(by_block, expected);
+
+        let (mut fee_rates, ts) = process_blocks(&blocks).unwrap();
+        fee_rates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(fee_rates, by_block.concat());
+        assert_eq!(last_block_ts, ts);
     }
 }
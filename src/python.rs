@@ -0,0 +1,241 @@
+//! Python bindings, build the `bitcoin_fee_model` module with `maturin develop --features python`
+
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::fee_bucket::FeeBuckets;
+use crate::matrix::Matrix;
+use crate::{get_model_high, get_model_low, Error, ModelData, Size1, Size20, Size64};
+
+fn to_py(e: Error) -> PyErr {
+    PyValueError::new_err(e.to_string().trim().to_string())
+}
+
+/// a single model with the shape of the bundled ones, see [`crate::ModelData`]
+#[pyclass(name = "Model")]
+pub struct PyModel(ModelData<Size20, Size64, Size1>);
+
+#[pymethods]
+impl PyModel {
+    /// the bundled model for 1 and 2 blocks
+    #[staticmethod]
+    fn low() -> Self {
+        PyModel(get_model_low())
+    }
+
+    /// the bundled model for 3 to 1008 blocks
+    #[staticmethod]
+    fn high() -> Self {
+        PyModel(get_model_high())
+    }
+
+    /// load the content of a `model.cbor` file
+    #[staticmethod]
+    fn from_cbor(bytes: &[u8]) -> PyResult<Self> {
+        ModelData::from_cbor(bytes).map(PyModel).map_err(to_py)
+    }
+
+    /// the input fields, in the order of the normalized values
+    #[getter]
+    fn fields(&self) -> Vec<String> {
+        self.0.fields.clone()
+    }
+
+    /// the normalized input of the `features` dict, missing features are 0
+    fn norm(&self, features: HashMap<String, f32>) -> PyResult<Vec<f32>> {
+        let input = self.0.norm(&features).map_err(to_py)?;
+        Ok(input[0].to_vec())
+    }
+
+    /// normalize and predict the `features` dict
+    fn predict(&self, features: HashMap<String, f32>) -> PyResult<f32> {
+        self.0.norm_predict(&features).map_err(to_py)
+    }
+
+    /// predict already normalized `values`, as returned by `norm`
+    fn predict_normalized(&self, values: Vec<f32>) -> PyResult<f32> {
+        if values.len() != self.0.fields.len() {
+            return Err(PyValueError::new_err(format!(
+                "expected {} values, found {}",
+                self.0.fields.len(),
+                values.len()
+            )));
+        }
+        let input = Matrix::from_array(values.into_boxed_slice());
+        Ok(self.0.predict(&input))
+    }
+}
+
+/// the low and high models, see [`crate::FeeModel`]
+#[pyclass(name = "FeeModel")]
+pub struct PyFeeModel(crate::FeeModel<Size64>);
+
+#[pymethods]
+impl PyFeeModel {
+    /// the bundled models
+    #[new]
+    fn new() -> Self {
+        PyFeeModel(crate::FeeModel::new(get_model_low(), get_model_high()))
+    }
+
+    /// load the content of the `model.cbor` files of the low and high models
+    #[staticmethod]
+    fn from_cbor(low: &[u8], high: &[u8]) -> PyResult<Self> {
+        let low = ModelData::from_cbor(low).map_err(to_py)?;
+        let high = ModelData::from_cbor(high).map_err(to_py)?;
+        Ok(PyFeeModel(crate::FeeModel::new(low, high)))
+    }
+
+    fn estimate(
+        &self,
+        block_target: u16,
+        timestamp: u32,
+        fee_rates: Vec<f64>,
+        last_block_ts: u32,
+    ) -> PyResult<f32> {
+        self.0
+            .estimate(block_target, Some(timestamp), &fee_rates, last_block_ts)
+            .map_err(to_py)
+    }
+
+    fn estimate_with_buckets(
+        &self,
+        block_target: u16,
+        timestamp: u32,
+        fee_buckets: Vec<u64>,
+        last_block_ts: u32,
+    ) -> PyResult<f32> {
+        self.0
+            .estimate_with_buckets(block_target, Some(timestamp), &fee_buckets, last_block_ts)
+            .map_err(to_py)
+    }
+
+    /// estimate from a `features` dict, like a dataset row
+    fn estimate_features(&self, features: HashMap<String, f32>) -> PyResult<f32> {
+        self.0.estimate_features(&features).map_err(to_py)
+    }
+
+    /// estimate every row, as returned by `DataFrame.to_dict("records")`
+    fn estimate_rows(&self, rows: Vec<HashMap<String, f32>>) -> PyResult<Vec<f32>> {
+        rows.iter()
+            .map(|row| self.0.estimate_features(row).map_err(to_py))
+            .collect()
+    }
+}
+
+/// fee buckets with a custom increment and upper limit, [`crate::fee_buckets()`] uses 50% and 500
+#[pyclass(name = "FeeBuckets")]
+pub struct PyFeeBuckets(FeeBuckets);
+
+#[pymethods]
+impl PyFeeBuckets {
+    #[new]
+    fn new(increment_percent: u32, upper_limit: f64) -> Self {
+        PyFeeBuckets(FeeBuckets::new(increment_percent, upper_limit))
+    }
+
+    /// the number of `rates` in every bucket
+    fn get(&self, rates: Vec<f64>) -> Vec<u64> {
+        self.0.get(&rates)
+    }
+}
+
+#[pyfunction]
+fn create_buckets_limits(increment_percent: u32, upper_limit: f64) -> Vec<f64> {
+    crate::fee_bucket::create_buckets_limits(increment_percent, upper_limit)
+}
+
+/// the fee buckets used by the bundled models
+#[pyfunction]
+fn fee_buckets(fee_rates: Vec<f64>) -> Vec<u64> {
+    crate::fee_buckets(&fee_rates)
+}
+
+#[pymodule]
+#[pyo3(name = "bitcoin_fee_model")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyModel>()?;
+    m.add_class::<PyFeeModel>()?;
+    m.add_class::<PyFeeBuckets>()?;
+    m.add_function(wrap_pyfunction!(create_buckets_limits, m)?)?;
+    m.add_function(wrap_pyfunction!(fee_buckets, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::python_module;
+    use pyo3::prelude::*;
+    use pyo3::types::{PyDict, PyModule};
+    use std::ffi::CString;
+
+    /// run `code` with the module imported as `m`
+    fn run(code: &str) {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "bitcoin_fee_model").unwrap();
+            python_module(&module).unwrap();
+            let globals = PyDict::new(py);
+            globals.set_item("m", module).unwrap();
+            let low = include_bytes!("../models/20211027-180849/model.cbor");
+            let high = include_bytes!("../models/20211027-180925/model.cbor");
+            globals.set_item("low", &low[..]).unwrap();
+            globals.set_item("high", &high[..]).unwrap();
+            let test = include_bytes!("../models/test_model.cbor");
+            globals.set_item("test", &test[..]).unwrap();
+            let code = CString::new(code).unwrap();
+            if let Err(e) = py.run(&code, Some(&globals), None) {
+                panic!("{}", e);
+            }
+        })
+    }
+
+    #[test]
+    fn test_python() {
+        run(r#"
+ts = 1613708045
+buckets = [13, 1, 32, 24, 14, 62, 1174, 453, 197, 291, 333, 3304, 307, 229, 36, 58]
+model = m.FeeModel()
+one = model.estimate_with_buckets(1, ts, buckets, ts - 300)
+assert one > model.estimate_with_buckets(2, ts, buckets, ts - 300)
+
+features = {"b%d" % i: b for i, b in enumerate(buckets)}
+features.update(confirms_in=1, delta_last=300, day_of_week=4, hour=4)
+assert model.estimate_features(features) == one
+assert model.estimate_rows([features, features]) == [one, one]
+assert m.FeeModel.from_cbor(low, high).estimate_features(features) == one
+
+low_model = m.Model.low()
+assert low_model.fields[0] == "confirms_in"
+assert low_model.predict(features) == one
+assert low_model.predict_normalized(low_model.norm(features)) == one
+assert m.Model.from_cbor(low).predict(features) == one
+
+fee_rates = [1.0, 1.2, 5.0, 5.1, 30.0]
+assert m.fee_buckets(fee_rates) == m.FeeBuckets(50, 500.0).get(fee_rates)
+assert sum(m.fee_buckets(fee_rates)) == 5
+assert model.estimate(6, ts, fee_rates, ts - 300) == model.estimate_with_buckets(6, ts, m.fee_buckets(fee_rates), ts - 300)
+assert m.create_buckets_limits(50, 500.0)[0] == 1.5
+assert len(m.create_buckets_limits(50, 500.0)) == len(buckets)
+"#);
+    }
+
+    #[test]
+    fn test_python_errors() {
+        run(r#"
+def raises(f):
+    try:
+        f()
+    except ValueError as e:
+        return str(e)
+    raise AssertionError("no error")
+
+assert raises(lambda: m.Model.from_cbor(test)).startswith("Invalid model: ")
+assert raises(lambda: m.Model.from_cbor(b"")).startswith("Invalid model: ")
+assert raises(lambda: m.FeeModel.from_cbor(low, test)).startswith("Invalid model: ")
+assert raises(lambda: m.Model.low().predict_normalized([0.0])) == "expected 20 values, found 1"
+"#);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::block_source::{invalid, BlockSource};
+use crate::Error;
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::{Block, BlockHash};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Authentication to the Bitcoin Core RPC
+#[derive(Debug, Clone)]
+pub enum Auth {
+    None,
+    UserPass(String, String),
+    /// path of the `.cookie` file in the data directory, read at every call since it changes
+    /// when the node restarts
+    CookieFile(PathBuf),
+}
+
+impl Auth {
+    fn header(&self) -> Result<Option<String>, Error> {
+        let credentials = match self {
+            Auth::None => return Ok(None),
+            Auth::UserPass(user, pass) => format!("{}:{}", user, pass),
+            Auth::CookieFile(path) => fs::read_to_string(path)
+                .map_err(|e| Error::BlockSource(format!("cannot read cookie file: {}", e)))?
+                .trim()
+                .to_string(),
+        };
+        Ok(Some(format!("Basic {}", base64(credentials.as_bytes()))))
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(CHARS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// [`BlockSource`] backed by the Bitcoin Core RPC
+pub struct RpcBlockSource {
+    url: String,
+    auth: Auth,
+    agent: ureq::Agent,
+}
+
+impl RpcBlockSource {
+    /// `url` like `http://127.0.0.1:8332`
+    pub fn new(url: &str, auth: Auth) -> Self {
+        RpcBlockSource {
+            url: url.to_string(),
+            auth,
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, Error> {
+        let body = json!({"jsonrpc": "1.0", "id": "bitcoin-fee-model", "method": method, "params": params});
+        let mut request = self.agent.post(&self.url);
+        if let Some(header) = self.auth.header()? {
+            request = request.set("Authorization", &header);
+        }
+        let response = match request.send_json(body) {
+            Ok(response) => response,
+            // Bitcoin Core returns RPC errors with an HTTP error status and a JSON body
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(Error::BlockSource(e.to_string())),
+        };
+        let status = response.status();
+        let mut value: Value = response
+            .into_json()
+            .map_err(|e| Error::BlockSource(format!("{} status {}", e, status)))?;
+        if !value["error"].is_null() {
+            return Err(Error::BlockSource(value["error"].to_string()));
+        }
+        Ok(value["result"].take())
+    }
+
+    /// the block decoded by Bitcoin Core with `getblock` verbosity 3, including the prevouts
+    pub fn block_verbose(&self, hash: &str) -> Result<Value, Error> {
+        self.call("getblock", json!([hash, 3]))
+    }
+}
+
+fn sat(btc: &Value) -> Result<u64, Error> {
+    let btc = btc.as_f64().ok_or_else(|| invalid("amount"))?;
+    Ok((btc * 100_000_000.0).round() as u64)
+}
+
+impl BlockSource for RpcBlockSource {
+    fn best_block_hash(&self) -> Result<BlockHash, Error> {
+        let hash = self.call("getbestblockhash", json!([]))?;
+        let hash = hash.as_str().ok_or_else(|| invalid("block hash"))?;
+        BlockHash::from_str(hash).map_err(|_| invalid("block hash"))
+    }
+
+    fn block(&self, hash: &BlockHash) -> Result<Block, Error> {
+        let hex = self.call("getblock", json!([hash.to_string(), 0]))?;
+        let hex = hex.as_str().ok_or_else(|| invalid("block"))?;
+        let bytes = Vec::<u8>::from_hex(hex).map_err(|_| invalid("block"))?;
+        deserialize(&bytes).map_err(|_| invalid("block"))
+    }
+
+    /// same result of [`crate::process_blocks()`] but using the prevouts returned by `getblock`
+    /// verbosity 3 instead of building the transactions map: a transaction fee rate is
+    /// considered only if all its inputs spend outputs created in the last 10 blocks
+    fn fee_data(&self) -> Result<(Vec<f64>, u32), Error> {
+        let mut hash = self.best_block_hash()?.to_string();
+        let mut blocks = vec![];
+        for _ in 0..10 {
+            let block = self.block_verbose(&hash)?;
+            hash = block["previousblockhash"]
+                .as_str()
+                .ok_or_else(|| invalid("previousblockhash"))?
+                .to_string();
+            blocks.push(block);
+        }
+        blocks.reverse();
+        let first_height = blocks[0]["height"]
+            .as_u64()
+            .ok_or_else(|| invalid("height"))?;
+
+        let mut last_block_ts = None;
+        let mut fee_rates = vec![];
+        for block in blocks.iter() {
+            let txs = block["tx"].as_array().ok_or_else(|| invalid("tx"))?;
+            if txs.len() > 1 && last_block_ts.is_none() {
+                last_block_ts = block["time"].as_u64().map(|t| t as u32);
+            }
+            for tx in txs {
+                let inputs = tx["vin"].as_array().ok_or_else(|| invalid("vin"))?;
+                let known = inputs.iter().all(|input| {
+                    input["prevout"]["height"]
+                        .as_u64()
+                        .map(|h| h >= first_height)
+                        .unwrap_or(false)
+                });
+                if !known {
+                    continue;
+                }
+                let fee = sat(&tx["fee"])?;
+                let weight = tx["weight"].as_f64().ok_or_else(|| invalid("weight"))?;
+                fee_rates.push(fee as f64 / (weight / 4.0));
+            }
+        }
+
+        Ok((fee_rates, last_block_ts.ok_or(Error::LastTsMissing)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64, Auth, RpcBlockSource};
+    use crate::block_files::tests::chain;
+    use crate::block_source::{BlockSource, MemoryBlockSource};
+    use crate::http_stub::serve;
+    use bitcoin::consensus::serialize;
+    use bitcoin::hashes::hex::ToHex;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"__cookie__:pass"), "X19jb29raWVfXzpwYXNz");
+    }
+
+    #[test]
+    fn test_rpc_block_source() {
+        let mut blocks = chain(11);
+        let tx = blocks[2].txdata[0].clone();
+        blocks[2].txdata.push(tx);
+        let tip = blocks.last().unwrap().block_hash();
+        let by_hash = blocks.clone();
+
+        let url = serve(move |request| {
+            if !request.headers.contains("Basic X19jb29raWVfXzpwYXNz") {
+                return (401, String::new());
+            }
+            let body: Value = serde_json::from_str(&request.body).unwrap();
+            let result = match body["method"].as_str().unwrap() {
+                "getbestblockhash" => json!(tip.to_string()),
+                "getblock" => {
+                    let hash = body["params"][0].as_str().unwrap();
+                    match by_hash.iter().find(|b| b.block_hash().to_string() == hash) {
+                        Some(block) => json!(serialize(block).to_hex()),
+                        None => {
+                            let error = json!({"code": -5, "message": "Block not found"});
+                            return (500, json!({"result": null, "error": error}).to_string());
+                        }
+                    }
+                }
+                _ => unreachable!(),
+            };
+            (200, json!({"result": result, "error": null}).to_string())
+        });
+
+        let cookie = std::env::temp_dir().join(format!("bfm-cookie-{}", std::process::id()));
+        std::fs::write(&cookie, "__cookie__:pass\n").unwrap();
+        let source = RpcBlockSource::new(&url, Auth::CookieFile(cookie.clone()));
+        let memory = MemoryBlockSource::new(blocks.clone());
+
+        assert_eq!(source.best_block_hash().unwrap(), tip);
+        assert_eq!(source.last_blocks(10).unwrap(), &blocks[1..]);
+        assert_eq!(source.block(&tip).unwrap(), memory.block(&tip).unwrap());
+        let err = source.last_blocks(12).unwrap_err().to_string();
+        assert!(err.contains("Block not found"), "{}", err);
+
+        let source = RpcBlockSource::new(&url, Auth::None);
+        assert!(source.best_block_hash().is_err());
+        std::fs::remove_file(cookie).unwrap();
+    }
+
+    #[test]
+    fn test_rpc_fee_data() {
+        // 11 blocks of heights 100..=110, block 105 has a transaction spending an output of
+        // block 102 and one spending an output of block 99
+        let url = serve(|request| {
+            let body: Value = serde_json::from_str(&request.body).unwrap();
+            let result = match body["method"].as_str().unwrap() {
+                "getbestblockhash" => json!(format!("{:064x}", 110)),
+                "getblock" => {
+                    let height =
+                        u64::from_str_radix(body["params"][0].as_str().unwrap(), 16).unwrap();
+                    let coinbase = json!({"vin": [{"coinbase": "00"}]});
+                    let mut txs = vec![coinbase];
+                    if height == 105 {
+                        let prevout = |h: u64| json!({"prevout": {"height": h}});
+                        txs.push(json!({"vin": [prevout(102)], "fee": 0.00001, "weight": 400}));
+                        txs.push(json!({"vin": [prevout(99)], "fee": 0.00001, "weight": 400}));
+                    }
+                    json!({
+                        "height": height,
+                        "time": height * 600,
+                        "previousblockhash": format!("{:064x}", height - 1),
+                        "tx": txs,
+                    })
+                }
+                _ => unreachable!(),
+            };
+            (200, json!({"result": result, "error": null}).to_string())
+        });
+
+        let source = RpcBlockSource::new(&url, Auth::UserPass("u".into(), "p".into()));
+        let (fee_rates, last_block_ts) = source.fee_data().unwrap();
+        assert_eq!(fee_rates, vec![10.0]);
+        assert_eq!(last_block_ts, 105 * 600);
+    }
+}
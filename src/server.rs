@@ -0,0 +1,251 @@
+use crate::block_source::BlockSource;
+use crate::block_window::BlockWindow;
+use crate::smart_fee::json_string;
+use crate::{Error, FeeModel, SizeMarker, MIN_RELAY_FEE};
+use bitcoin::Block;
+use std::io;
+use std::sync::{Mutex, RwLock};
+use tiny_http::{Header, Response};
+
+/// block targets returned by `/fee-estimates`, same as the Esplora API
+pub const ESPLORA_TARGETS: [u16; 28] = [
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 144,
+    504, 1008,
+];
+
+/// HTTP server exposing the estimates in the shape of common fee APIs:
+/// * `/fee-estimates` map from block target to fee rate in sat/vbytes, like Esplora
+/// * `/v1/fees/recommended` `fastestFee`, `halfHourFee`, `hourFee`, `economyFee` and `minimumFee`
+///   in sat/vbytes, like mempool.space
+pub struct FeeServer<N> {
+    model: FeeModel<N>,
+    window: Mutex<BlockWindow>,
+    /// the output of [`BlockWindow::process`] if the window is full
+    processed: RwLock<Option<(Vec<f64>, u32)>>,
+}
+
+impl<N: SizeMarker> FeeServer<N> {
+    pub fn new(model: FeeModel<N>) -> Self {
+        FeeServer {
+            model,
+            window: Mutex::new(BlockWindow::new()),
+            processed: RwLock::new(None),
+        }
+    }
+
+    /// add a new tip to the block window, estimates are available once the window is full
+    pub fn push_block(&self, block: Block) {
+        let mut window = self.window.lock().unwrap();
+        window.push(block);
+        *self.processed.write().unwrap() = window.process().ok();
+    }
+
+    /// push the blocks of `source` missing from the window, up to the last 10, returns `true` if
+    /// the tip changed. Call it periodically to keep the estimates up to date
+    pub fn update(&self, source: &dyn BlockSource) -> Result<bool, Error> {
+        let best = source.best_block_hash()?;
+        let mut window = self.window.lock().unwrap();
+        if window.tip().map(|b| b.block_hash()) == Some(best) {
+            return Ok(false);
+        }
+        for block in source.last_blocks(10)? {
+            window.push(block);
+        }
+        *self.processed.write().unwrap() = window.process().ok();
+        Ok(true)
+    }
+
+    fn estimates(&self, targets: &[u16], timestamp: Option<u32>) -> Result<Vec<f32>, Error> {
+        let processed = self.processed.read().unwrap();
+        let (fee_rates, last_block_ts) = processed.as_ref().ok_or(Error::LastTsMissing)?;
+        targets
+            .iter()
+            .map(|t| {
+                self.model
+                    .estimate(*t, timestamp, fee_rates, *last_block_ts)
+            })
+            .collect()
+    }
+
+    /// returns the status code and the JSON body for the request of `url`
+    pub fn handle(&self, url: &str, timestamp: Option<u32>) -> (u16, String) {
+        let path = url.split('?').next().unwrap_or(url);
+        let result = match path {
+            "/fee-estimates" => self.estimates(&ESPLORA_TARGETS, timestamp).map(|e| {
+                let fields: Vec<String> = ESPLORA_TARGETS
+                    .iter()
+                    .zip(e)
+                    .map(|(t, fee_rate)| format!("\"{}\":{}", t, json_number(fee_rate, 3)))
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            }),
+            "/v1/fees/recommended" => self.estimates(&[1, 3, 6, 144], timestamp).map(|e| {
+                let min = MIN_RELAY_FEE as f32;
+                // `max` would turn NaN into the minimum
+                let fee = |fee_rate: f32| match fee_rate {
+                    f if f.is_finite() => json_number(f.max(min).ceil(), 0),
+                    f => json_number(f, 0),
+                };
+                format!(
+                    "{{\"fastestFee\":{},\"halfHourFee\":{},\"hourFee\":{},\"economyFee\":{},\"minimumFee\":{:.0}}}",
+                    fee(e[0]),
+                    fee(e[1]),
+                    fee(e[2]),
+                    fee(e[3]),
+                    min
+                )
+            }),
+            _ => return (404, "{\"error\":\"not found\"}".to_string()),
+        };
+        match result {
+            Ok(body) => (200, body),
+            Err(e) => (
+                503,
+                format!("{{\"error\":{}}}", json_string(e.to_string().trim())),
+            ),
+        }
+    }
+
+    /// serve requests on `addr` until an error occurs
+    // `io::Error::other` is not available on the minimum supported rust version
+    #[allow(clippy::io_other_error)]
+    pub fn run(&self, addr: &str) -> io::Result<()> {
+        let server = tiny_http::Server::http(addr)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        self.serve(&server)
+    }
+
+    /// serve requests received by `server`
+    pub fn serve(&self, server: &tiny_http::Server) -> io::Result<()> {
+        let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+        for request in server.incoming_requests() {
+            let (status, body) = self.handle(request.url(), None);
+            let response = Response::from_string(body)
+                .with_status_code(status)
+                .with_header(content_type.clone());
+            request.respond(response)?;
+        }
+        Ok(())
+    }
+}
+
+/// `value` with `precision` decimals, `null` if it's not finite
+fn json_number(value: f32, precision: usize) -> String {
+    if value.is_finite() {
+        format!("{:.*}", precision, value)
+    } else {
+        "null".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FeeServer;
+    use crate::block_files::tests::chain;
+    use crate::block_source::MemoryBlockSource;
+    use crate::{get_model_high, get_model_low, FeeModel, FieldsDescribe};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+
+    fn server() -> FeeServer<crate::Size64> {
+        FeeServer::new(FeeModel::new(get_model_low(), get_model_high()))
+    }
+
+    #[test]
+    fn test_handle() {
+        let server = server();
+        let ts = Some(1613708045);
+        let (status, body) = server.handle("/fee-estimates", ts);
+        assert_eq!(status, 503);
+        assert!(body.starts_with("{\"error\":\""), "{}", body);
+        assert!(body.ends_with("\"}"), "{}", body);
+        assert_eq!(server.handle("/other", ts).0, 404);
+
+        let mut blocks = chain(10);
+        let tx = blocks[0].txdata[0].clone();
+        blocks[0].txdata.push(tx);
+        for block in blocks {
+            server.push_block(block);
+        }
+        let (status, body) = server.handle("/fee-estimates", ts);
+        assert_eq!(status, 200);
+        assert!(body.starts_with("{\"1\":"), "{}", body);
+        assert!(body.contains(",\"1008\":"), "{}", body);
+        assert_eq!(server.handle("/fee-estimates?x=1", ts).0, 200);
+
+        let (status, body) = server.handle("/v1/fees/recommended", ts);
+        assert_eq!(status, 200);
+        assert!(body.contains("\"minimumFee\":1}"), "{}", body);
+    }
+
+    #[test]
+    fn test_handle_non_finite() {
+        // a zero std gives non finite normalized inputs and estimates
+        let model = || {
+            let mut model = get_model_low();
+            let describe = |value| model.fields.iter().map(move |f| (f.clone(), value));
+            model.norm = FieldsDescribe::new(describe(0.0), describe(0.0));
+            model
+        };
+        let server = FeeServer::new(FeeModel::new(model(), model()));
+        let mut blocks = chain(10);
+        let tx = blocks[0].txdata[0].clone();
+        blocks[0].txdata.push(tx);
+        for block in blocks {
+            server.push_block(block);
+        }
+        let ts = Some(1613708045);
+        for url in ["/fee-estimates", "/v1/fees/recommended"].iter() {
+            let (status, body) = server.handle(url, ts);
+            assert_eq!(status, 200);
+            assert!(body.contains(":null"), "{}", body);
+            assert!(!body.contains("NaN") && !body.contains("inf"), "{}", body);
+        }
+        let (_, body) = server.handle("/v1/fees/recommended", ts);
+        assert!(body.ends_with("\"minimumFee\":1}"), "{}", body);
+    }
+
+    #[test]
+    fn test_update() {
+        let server = server();
+        let mut blocks = chain(12);
+        let tx = blocks[2].txdata[0].clone();
+        blocks[2].txdata.push(tx);
+        let mut source = MemoryBlockSource::new(blocks[..11].to_vec());
+        assert!(server.update(&source).unwrap());
+        assert!(!server.update(&source).unwrap());
+        assert_eq!(server.handle("/fee-estimates", Some(1613708045)).0, 200);
+
+        // a reorg of the tip followed by a new block
+        let mut fork = blocks[10].clone();
+        fork.header.time += 1;
+        let mut next = blocks[11].clone();
+        next.header.prev_blockhash = fork.block_hash();
+        source.push(fork);
+        source.push(next.clone());
+        assert!(server.update(&source).unwrap());
+        assert_eq!(server.window.lock().unwrap().tip(), Some(&next));
+        assert_eq!(server.handle("/fee-estimates", Some(1613708045)).0, 200);
+    }
+
+    #[test]
+    fn test_serve() {
+        let http = Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+        let addr = http.server_addr().to_ip().unwrap();
+        let cloned = http.clone();
+        std::thread::spawn(move || server().serve(&cloned));
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(
+                b"GET /fee-estimates HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            )
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503"), "{}", response);
+        assert!(response.contains("application/json"), "{}", response);
+        http.unblock();
+    }
+}
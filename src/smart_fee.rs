@@ -0,0 +1,122 @@
+use crate::{Error, FeeModel, SizeMarker, MAX_BLOCK_TARGET, MIN_RELAY_FEE};
+
+/// Result in the same shape of the Bitcoin Core `estimatesmartfee` RPC
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmartFee {
+    /// fee rate in BTC/kvB
+    pub feerate: Option<f64>,
+    pub errors: Vec<String>,
+    /// block target the estimate is for
+    pub blocks: u16,
+}
+
+impl SmartFee {
+    /// `estimate` in sat/vbytes is floored at [`MIN_RELAY_FEE`] like Bitcoin Core does, a non
+    /// finite estimate is reported in `errors`
+    pub fn new(block_target: u16, estimate: Result<f32, Error>) -> Self {
+        match estimate {
+            Ok(fee_rate) if !fee_rate.is_finite() => SmartFee {
+                feerate: None,
+                errors: vec![format!("Estimated fee rate {} is not finite", fee_rate)],
+                blocks: 0,
+            },
+            Ok(fee_rate) => SmartFee {
+                feerate: Some(fee_rate.max(MIN_RELAY_FEE as f32) as f64 / 100_000.0),
+                errors: vec![],
+                blocks: block_target,
+            },
+            Err(e) => SmartFee {
+                feerate: None,
+                errors: vec![e.to_string().trim().to_string()],
+                blocks: 0,
+            },
+        }
+    }
+
+    /// the JSON returned by `estimatesmartfee`, `errors` is omitted if empty
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![];
+        if let Some(feerate) = self.feerate {
+            fields.push(format!("\"feerate\":{:.8}", feerate));
+        }
+        if !self.errors.is_empty() {
+            let errors: Vec<String> = self.errors.iter().map(|e| json_string(e)).collect();
+            fields.push(format!("\"errors\":[{}]", errors.join(",")));
+        }
+        fields.push(format!("\"blocks\":{}", self.blocks));
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+impl<N: SizeMarker, O: SizeMarker> FeeModel<N, O> {
+    /// like [`FeeModel::estimate`] but returning the shape of the `estimatesmartfee` RPC,
+    /// `block_target` is clamped between 1 and [`MAX_BLOCK_TARGET`]
+    // `Ord::clamp` is not available on the minimum supported rust version
+    #[allow(clippy::manual_clamp)]
+    pub fn estimate_smart_fee(
+        &self,
+        block_target: u16,
+        timestamp: Option<u32>,
+        fee_rates: &[f64],
+        last_block_ts: u32,
+    ) -> SmartFee {
+        let block_target = block_target.max(1).min(MAX_BLOCK_TARGET);
+        let estimate = self.estimate(block_target, timestamp, fee_rates, last_block_ts);
+        SmartFee::new(block_target, estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SmartFee;
+    use crate::{get_model_high, get_model_low, Error, FeeModel};
+
+    #[test]
+    fn test_smart_fee_json() {
+        let smart_fee = SmartFee::new(2, Ok(12.345));
+        assert_eq!(smart_fee.to_json(), "{\"feerate\":0.00012345,\"blocks\":2}");
+
+        let smart_fee = SmartFee::new(2, Ok(0.1));
+        assert_eq!(smart_fee.to_json(), "{\"feerate\":0.00001000,\"blocks\":2}");
+
+        let smart_fee = SmartFee::new(2, Err(Error::MissingStdData("b\"0".to_string())));
+        assert_eq!(
+            smart_fee.to_json(),
+            "{\"errors\":[\"Missing std field b\\\"0\"],\"blocks\":0}"
+        );
+
+        for fee_rate in [f32::NAN, f32::INFINITY].iter() {
+            let smart_fee = SmartFee::new(2, Ok(*fee_rate));
+            assert_eq!(smart_fee.feerate, None);
+            let json = smart_fee.to_json();
+            assert!(
+                json.starts_with("{\"errors\":[\"Estimated fee rate "),
+                "{}",
+                json
+            );
+            assert!(json.ends_with("is not finite\"],\"blocks\":0}"), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_estimate_smart_fee() {
+        let model = FeeModel::new(get_model_low(), get_model_high());
+        let smart_fee = model.estimate_smart_fee(2000, Some(1613708045), &[1.0], 1613707745);
+        assert_eq!(smart_fee.blocks, 1008);
+        assert!(smart_fee.feerate.is_some());
+    }
+}
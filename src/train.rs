@@ -0,0 +1,534 @@
+use crate::{Error, FEATURES};
+use bitcoin_fee_model_codegen::{RawFieldsDescribe, RawModelData, RawWeights};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A dataset row, features and label by column name
+pub type Row = HashMap<String, f32>;
+
+/// Training parameters, the defaults reproduce the architecture of the bundled models
+#[derive(Debug, Clone)]
+pub struct TrainConfig {
+    /// input features, in the order of the model fields
+    pub fields: Vec<String>,
+    pub label: String,
+    /// neurons of both the hidden layers
+    pub neurons: usize,
+    /// slope of the leaky relu for negative values
+    pub alpha: f32,
+    /// if not empty, one output per quantile trained with the pinball loss, otherwise a single
+    /// output trained with the mean squared error
+    pub quantiles: Vec<f32>,
+    pub epochs: usize,
+    pub batch_size: usize,
+    /// of the Adam optimizer
+    pub learning_rate: f32,
+    /// of the weights initialization and the shuffling, same seed and dataset give the same model
+    pub seed: u64,
+    /// seconds east of UTC of the time features in the dataset, stored in the model so that
+    /// they are computed in the same timezone when estimating
+    pub utc_offset: i32,
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        TrainConfig {
+            fields: FEATURES.iter().map(|f| f.to_string()).collect(),
+            label: "fee_rate".to_string(),
+            neurons: 64,
+            alpha: 0.05,
+            quantiles: vec![],
+            epochs: 20,
+            batch_size: 32,
+            learning_rate: 0.001,
+            seed: 0,
+            utc_offset: 0,
+        }
+    }
+}
+
+/// Parse a csv with an header containing the column names
+pub fn read_csv(csv: &str) -> Result<Vec<Row>, Error> {
+    let mut lines = csv.lines();
+    let header: Vec<&str> = match lines.next() {
+        Some(header) => header.split(',').map(str::trim).collect(),
+        None => return Err(Error::InvalidDataset("empty csv".to_string())),
+    };
+    let mut rows = vec![];
+    for (i, line) in lines.enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let values: Vec<&str> = line.split(',').collect();
+        if values.len() != header.len() {
+            return Err(Error::InvalidDataset(format!("line {} columns", i + 2)));
+        }
+        let mut row = HashMap::new();
+        for (name, value) in header.iter().zip(values) {
+            let value = value
+                .trim()
+                .parse()
+                .map_err(|_| Error::InvalidDataset(format!("line {} {}", i + 2, name)))?;
+            row.insert(name.to_string(), value);
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// xorshift64*, enough for weights initialization and shuffling without dependencies
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed ^ 0x9e37_79b9_7f4a_7c15 | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// uniform in `[-limit, limit)`
+    fn uniform(&mut self, limit: f32) -> f32 {
+        let unit = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+        (unit * 2.0 - 1.0) * limit
+    }
+
+    fn shuffle<T>(&mut self, v: &mut [T]) {
+        for i in (1..v.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            v.swap(i, j);
+        }
+    }
+}
+
+/// A parameter with the Adam moments and the accumulated gradient
+struct Param {
+    value: Vec<f32>,
+    grad: Vec<f32>,
+    m: Vec<f32>,
+    v: Vec<f32>,
+}
+
+impl Param {
+    fn new(value: Vec<f32>) -> Self {
+        let len = value.len();
+        Param {
+            value,
+            grad: vec![0.0; len],
+            m: vec![0.0; len],
+            v: vec![0.0; len],
+        }
+    }
+
+    fn step(&mut self, learning_rate: f32, t: i32, batch: usize) {
+        const B1: f32 = 0.9;
+        const B2: f32 = 0.999;
+        const EPSILON: f32 = 1e-7;
+        let lr = learning_rate * (1.0 - B2.powi(t)).sqrt() / (1.0 - B1.powi(t));
+        for i in 0..self.value.len() {
+            let g = self.grad[i] / batch as f32;
+            self.m[i] = B1 * self.m[i] + (1.0 - B1) * g;
+            self.v[i] = B2 * self.v[i] + (1.0 - B2) * g * g;
+            self.value[i] -= lr * self.m[i] / (self.v[i].sqrt() + EPSILON);
+            self.grad[i] = 0.0;
+        }
+    }
+}
+
+/// A dense layer, the kernel is stored by input like the Keras one
+struct Dense {
+    inputs: usize,
+    outputs: usize,
+    kernel: Param,
+    bias: Param,
+}
+
+impl Dense {
+    /// Glorot uniform initialization, the Keras default
+    fn new(inputs: usize, outputs: usize, rng: &mut Rng) -> Self {
+        let limit = (6.0 / (inputs + outputs) as f32).sqrt();
+        let kernel = (0..inputs * outputs).map(|_| rng.uniform(limit)).collect();
+        Dense {
+            inputs,
+            outputs,
+            kernel: Param::new(kernel),
+            bias: Param::new(vec![0.0; outputs]),
+        }
+    }
+
+    fn forward(&self, x: &[f32]) -> Vec<f32> {
+        let mut z = self.bias.value.clone();
+        for (i, x) in x.iter().enumerate() {
+            let row = &self.kernel.value[i * self.outputs..(i + 1) * self.outputs];
+            for (z, w) in z.iter_mut().zip(row) {
+                *z += x * w;
+            }
+        }
+        z
+    }
+
+    /// accumulate the gradients given the input `x` and the gradient `dz` of the output,
+    /// returns the gradient of the input
+    fn backward(&mut self, x: &[f32], dz: &[f32]) -> Vec<f32> {
+        let mut dx = vec![0.0; self.inputs];
+        for (i, x) in x.iter().enumerate() {
+            for (j, dz) in dz.iter().enumerate() {
+                self.kernel.grad[i * self.outputs + j] += x * dz;
+                dx[i] += self.kernel.value[i * self.outputs + j] * dz;
+            }
+        }
+        for (g, dz) in self.bias.grad.iter_mut().zip(dz) {
+            *g += dz;
+        }
+        dx
+    }
+
+    fn step(&mut self, learning_rate: f32, t: i32, batch: usize) {
+        self.kernel.step(learning_rate, t, batch);
+        self.bias.step(learning_rate, t, batch);
+    }
+
+    fn raw_kernel(&self) -> Vec<Vec<f32>> {
+        self.kernel
+            .value
+            .chunks(self.outputs)
+            .map(|c| c.to_vec())
+            .collect()
+    }
+}
+
+fn leaky_relu(z: &[f32], alpha: f32) -> Vec<f32> {
+    z.iter()
+        .map(|z| if *z > 0.0 { *z } else { alpha * z })
+        .collect()
+}
+
+fn leaky_relu_backward(z: &[f32], dh: &[f32], alpha: f32) -> Vec<f32> {
+    z.iter()
+        .zip(dh)
+        .map(|(z, dh)| if *z > 0.0 { *dh } else { alpha * dh })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct TestVector {
+    result: f32,
+    test_vector: Vec<f32>,
+}
+
+/// A model trained by [`train`]
+pub struct TrainedModel {
+    fields: Vec<String>,
+    mean: Vec<f32>,
+    std: Vec<f32>,
+    alpha: f32,
+    quantiles: Vec<f32>,
+    utc_offset: i32,
+    layers: [Dense; 3],
+    /// the first row of the dataset, used as test vector
+    test_vector: Vec<f32>,
+    /// mean loss of every epoch
+    pub losses: Vec<f32>,
+}
+
+impl TrainedModel {
+    fn normalize(&self, values: &[f32]) -> Vec<f32> {
+        values
+            .iter()
+            .zip(self.mean.iter().zip(self.std.iter()))
+            .map(|(x, (mean, std))| (x - mean) / std)
+            .collect()
+    }
+
+    fn forward(&self, x: &[f32]) -> Vec<f32> {
+        let h1 = leaky_relu(&self.layers[0].forward(x), self.alpha);
+        let h2 = leaky_relu(&self.layers[1].forward(&h1), self.alpha);
+        self.layers[2].forward(&h2)
+    }
+
+    /// the outputs of the model for the not normalized `features`, missing features are 0
+    pub fn predict(&self, features: &Row) -> Vec<f32> {
+        let values: Vec<f32> = self
+            .fields
+            .iter()
+            .map(|f| features.get(f).cloned().unwrap_or(0.0))
+            .collect();
+        self.forward(&self.normalize(&values))
+    }
+
+    /// the content of the `model.cbor` file loaded by `build.rs` and `ModelData::from_cbor`
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let describe = |values: &[f32]| {
+            self.fields
+                .iter()
+                .cloned()
+                .zip(values.iter().cloned())
+                .collect()
+        };
+        let raw = RawModelData {
+            norm: RawFieldsDescribe {
+                mean: describe(&self.mean),
+                std: describe(&self.std),
+            },
+            weights: RawWeights {
+                l0_bias: self.layers[0].bias.value.clone(),
+                l0_kernel: self.layers[0].raw_kernel(),
+                l1_bias: self.layers[1].bias.value.clone(),
+                l1_kernel: self.layers[1].raw_kernel(),
+                l2_bias: self.layers[2].bias.value.clone(),
+                l2_kernel: self.layers[2].raw_kernel(),
+            },
+            fields: self.fields.clone(),
+            alpha: self.alpha,
+            quantiles: self.quantiles.clone(),
+            utc_offset: self.utc_offset,
+        };
+        serde_cbor::to_vec(&raw).expect("serializing to vec doesn't fail")
+    }
+
+    /// the content of the `test_vector.cbor` file: the first row of the dataset and the first
+    /// output of the model
+    pub fn test_vector_cbor(&self) -> Vec<u8> {
+        let test = TestVector {
+            result: self.forward(&self.normalize(&self.test_vector))[0],
+            test_vector: self.test_vector.clone(),
+        };
+        serde_cbor::to_vec(&test).expect("serializing to vec doesn't fail")
+    }
+}
+
+/// Train a model with two hidden layers of `config.neurons` with leaky relu activation on the
+/// `rows` normalized with their mean and standard deviation
+pub fn train(rows: &[Row], config: &TrainConfig) -> Result<TrainedModel, Error> {
+    if rows.len() < 2 {
+        return Err(Error::InvalidDataset("less than 2 rows".to_string()));
+    }
+    if config.quantiles.windows(2).any(|w| w[0] >= w[1])
+        || config.quantiles.iter().any(|q| !(0.0..=1.0).contains(q))
+    {
+        return Err(Error::InvalidModel(
+            "quantiles must be strictly increasing probabilities".to_string(),
+        ));
+    }
+    let column = |row: &Row, name: &str| {
+        row.get(name)
+            .cloned()
+            .ok_or_else(|| Error::InvalidDataset(format!("missing column {}", name)))
+    };
+    let mut inputs = Vec::with_capacity(rows.len());
+    let mut labels = Vec::with_capacity(rows.len());
+    for row in rows {
+        let values = config
+            .fields
+            .iter()
+            .map(|f| column(row, f))
+            .collect::<Result<Vec<_>, _>>()?;
+        inputs.push(values);
+        labels.push(column(row, &config.label)?);
+    }
+
+    // sample standard deviation, as the pandas `describe` used for the bundled models
+    let n = rows.len() as f32;
+    let fields = config.fields.len();
+    let mean: Vec<f32> = (0..fields)
+        .map(|i| inputs.iter().map(|x| x[i]).sum::<f32>() / n)
+        .collect();
+    let std: Vec<f32> = (0..fields)
+        .map(|i| {
+            let var = inputs.iter().map(|x| (x[i] - mean[i]).powi(2)).sum::<f32>() / (n - 1.0);
+            if var > 0.0 {
+                var.sqrt()
+            } else {
+                1.0
+            }
+        })
+        .collect();
+
+    let mut rng = Rng::new(config.seed);
+    let outputs = config.quantiles.len().max(1);
+    let mut model = TrainedModel {
+        fields: config.fields.clone(),
+        mean,
+        std,
+        alpha: config.alpha,
+        quantiles: config.quantiles.clone(),
+        utc_offset: config.utc_offset,
+        layers: [
+            Dense::new(fields, config.neurons, &mut rng),
+            Dense::new(config.neurons, config.neurons, &mut rng),
+            Dense::new(config.neurons, outputs, &mut rng),
+        ],
+        test_vector: inputs[0].clone(),
+        losses: vec![],
+    };
+    let inputs: Vec<Vec<f32>> = inputs.iter().map(|x| model.normalize(x)).collect();
+
+    let alpha = config.alpha;
+    let mut indexes: Vec<usize> = (0..rows.len()).collect();
+    let mut t = 0;
+    for _ in 0..config.epochs {
+        rng.shuffle(&mut indexes);
+        let mut epoch_loss = 0.0;
+        for batch in indexes.chunks(config.batch_size.max(1)) {
+            for &i in batch {
+                let x = &inputs[i];
+                let [l0, l1, l2] = &mut model.layers;
+                let z1 = l0.forward(x);
+                let h1 = leaky_relu(&z1, alpha);
+                let z2 = l1.forward(&h1);
+                let h2 = leaky_relu(&z2, alpha);
+                let y = l2.forward(&h2);
+
+                let (loss, dy) = loss(&y, labels[i], &config.quantiles);
+                epoch_loss += loss;
+
+                let dh2 = l2.backward(&h2, &dy);
+                let dh1 = l1.backward(&h1, &leaky_relu_backward(&z2, &dh2, alpha));
+                l0.backward(x, &leaky_relu_backward(&z1, &dh1, alpha));
+            }
+            t += 1;
+            for layer in model.layers.iter_mut() {
+                layer.step(config.learning_rate, t, batch.len());
+            }
+        }
+        model.losses.push(epoch_loss / n);
+    }
+    Ok(model)
+}
+
+/// the loss of the outputs `y` and its gradient
+fn loss(y: &[f32], label: f32, quantiles: &[f32]) -> (f32, Vec<f32>) {
+    if quantiles.is_empty() {
+        let e = y[0] - label;
+        return (e * e, vec![2.0 * e]);
+    }
+    let mut loss = 0.0;
+    let mut dy = Vec::with_capacity(y.len());
+    for (y, q) in y.iter().zip(quantiles) {
+        let e = label - y;
+        if e > 0.0 {
+            loss += q * e;
+            dy.push(-q);
+        } else {
+            loss += (q - 1.0) * e;
+            dy.push(1.0 - q);
+        }
+    }
+    (
+        loss / quantiles.len() as f32,
+        dy.iter().map(|d| d / quantiles.len() as f32).collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_csv, train, Row, TrainConfig};
+    use crate::matrix::size::*;
+    use crate::tests::assert_approx_eq;
+    use crate::{Error, ModelData};
+    use serde::Deserialize;
+
+    /// `fee_rate` depends on the first bucket and on the hour
+    fn dataset() -> Vec<Row> {
+        let mut csv = "b0,hour,fee_rate\n".to_string();
+        for i in 0..200 {
+            let b0 = (i * 7 % 50) as f32;
+            let hour = (i % 24) as f32;
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                b0,
+                hour,
+                2.0 + 0.5 * b0 + 0.1 * hour
+            ));
+        }
+        read_csv(&csv).unwrap()
+    }
+
+    fn config() -> TrainConfig {
+        TrainConfig {
+            fields: vec!["b0".to_string(), "hour".to_string()],
+            neurons: 8,
+            epochs: 30,
+            batch_size: 8,
+            learning_rate: 0.01,
+            ..Default::default()
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct TestVector {
+        result: f32,
+        test_vector: Vec<f32>,
+    }
+
+    #[test]
+    fn test_train() {
+        let rows = dataset();
+        let model = train(&rows, &config()).unwrap();
+        let first = model.losses[0];
+        let last = *model.losses.last().unwrap();
+        assert!(last < first / 10.0, "{} {}", first, last);
+
+        // the written files are loaded with the same predictions
+        let loaded: ModelData<Size2, Size8, Size1> =
+            ModelData::from_cbor(&model.to_cbor()).unwrap();
+        assert_approx_eq(
+            loaded.norm_predict(&rows[5]).unwrap(),
+            model.predict(&rows[5])[0],
+        );
+        let test: TestVector = serde_cbor::from_slice(&model.test_vector_cbor()).unwrap();
+        assert_eq!(test.test_vector, vec![0.0, 0.0]);
+        assert_approx_eq(loaded.norm_predict(&rows[0]).unwrap(), test.result);
+        assert_eq!(loaded.utc_offset, 0);
+
+        let eastern = TrainConfig {
+            utc_offset: -5 * 3600,
+            ..config()
+        };
+        let eastern = train(&rows, &eastern).unwrap();
+        let loaded: ModelData<Size2, Size8, Size1> =
+            ModelData::from_cbor(&eastern.to_cbor()).unwrap();
+        assert_eq!(loaded.utc_offset, -5 * 3600);
+
+        // deterministic given the seed
+        let again = train(&rows, &config()).unwrap();
+        assert_eq!(again.to_cbor(), model.to_cbor());
+    }
+
+    #[test]
+    fn test_train_quantiles() {
+        let config = TrainConfig {
+            quantiles: vec![0.1, 0.5, 0.9],
+            ..config()
+        };
+        let model = train(&dataset(), &config).unwrap();
+        let loaded: ModelData<Size2, Size8, Size3> =
+            ModelData::from_cbor(&model.to_cbor()).unwrap();
+        assert_eq!(loaded.quantiles, vec![0.1, 0.5, 0.9]);
+
+        let config = TrainConfig {
+            quantiles: vec![0.5, 0.1],
+            ..config
+        };
+        assert!(matches!(
+            train(&dataset(), &config),
+            Err(Error::InvalidModel(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_csv() {
+        assert!(matches!(read_csv(""), Err(Error::InvalidDataset(_))));
+        assert!(matches!(read_csv("a,b\n1"), Err(Error::InvalidDataset(_))));
+        assert!(matches!(read_csv("a\nx"), Err(Error::InvalidDataset(_))));
+        let rows = read_csv("a, b\n1,2\n\n").unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["b"], 2.0);
+        let config = TrainConfig::default();
+        assert!(matches!(
+            train(&rows, &config),
+            Err(Error::InvalidDataset(_))
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+//! WebAssembly bindings, there is no clock in the browser so timestamps are always given
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{get_model_high, get_model_low, Error, Size64};
+
+fn to_js(e: Error) -> JsValue {
+    JsValue::from_str(e.to_string().trim())
+}
+
+/// the bundled models, see [`crate::FeeModel`]
+#[wasm_bindgen(js_name = FeeModel)]
+pub struct WasmFeeModel(crate::FeeModel<Size64>);
+
+#[wasm_bindgen(js_class = FeeModel)]
+impl WasmFeeModel {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmFeeModel {
+        WasmFeeModel(crate::FeeModel::new(get_model_low(), get_model_high()))
+    }
+
+    /// fee rate in sat/vbytes to confirm within `block_target`, see [`crate::FeeModel::estimate`]
+    pub fn estimate(
+        &self,
+        block_target: u16,
+        timestamp: u32,
+        fee_rates: &[f64],
+        last_block_ts: u32,
+    ) -> Result<f32, JsValue> {
+        self.0
+            .estimate(block_target, Some(timestamp), fee_rates, last_block_ts)
+            .map_err(to_js)
+    }
+
+    /// same as `estimate` with the buckets returned by `feeBuckets`
+    #[wasm_bindgen(js_name = estimateWithBuckets)]
+    pub fn estimate_with_buckets(
+        &self,
+        block_target: u16,
+        timestamp: u32,
+        fee_buckets: &[u64],
+        last_block_ts: u32,
+    ) -> Result<f32, JsValue> {
+        self.0
+            .estimate_with_buckets(block_target, Some(timestamp), fee_buckets, last_block_ts)
+            .map_err(to_js)
+    }
+}
+
+impl Default for WasmFeeModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// the fee buckets of `fee_rates`, see [`crate::fee_buckets`]
+#[wasm_bindgen(js_name = feeBuckets)]
+pub fn fee_buckets(fee_rates: &[f64]) -> Vec<u64> {
+    crate::fee_buckets(fee_rates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fee_buckets, WasmFeeModel};
+    use crate::model_data::tests::BUCKETS;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+    #[test]
+    fn test_estimate() {
+        let model = WasmFeeModel::new();
+        let ts = 1613708045u32;
+        let one = model
+            .estimate_with_buckets(1, ts, &BUCKETS, ts - 300)
+            .unwrap();
+        let two = model
+            .estimate_with_buckets(2, ts, &BUCKETS, ts - 300)
+            .unwrap();
+        assert!(one > two, "1 block ({}) > 2 ({})", one, two);
+
+        let fee_rates = [1.0, 1.2, 5.0, 5.1, 30.0];
+        let buckets = fee_buckets(&fee_rates);
+        assert_eq!(buckets.iter().sum::<u64>(), 5);
+        assert_eq!(
+            model.estimate(6, ts, &fee_rates, ts - 300).unwrap(),
+            model
+                .estimate_with_buckets(6, ts, &buckets, ts - 300)
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use crate::block_source::BlockSource;
+use crate::block_window::BlockWindow;
+use crate::{Error, FeeModel, SizeMarker};
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::Hash;
+use bitcoin::{Block, BlockHash};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+
+/// The estimates computed after the last block
+#[derive(Debug, Clone, PartialEq)]
+pub struct EstimateCurve {
+    pub tip: BlockHash,
+    /// fee rate in sat/vbytes by block target, targets failing the estimation are missing
+    pub estimates: BTreeMap<u16, f32>,
+}
+
+/// Thread safe access to the latest [`EstimateCurve`] of a [`ZmqListener`]
+#[derive(Debug, Clone, Default)]
+pub struct EstimatesHandle(Arc<RwLock<Option<EstimateCurve>>>);
+
+impl EstimatesHandle {
+    /// None until the block window is full
+    pub fn latest(&self) -> Option<EstimateCurve> {
+        self.0.read().unwrap().clone()
+    }
+
+    /// the estimate of the lowest target in the curve greater or equal than `block_target`
+    pub fn estimate(&self, block_target: u16) -> Option<f32> {
+        let curve = self.0.read().unwrap();
+        let (_, fee_rate) = curve.as_ref()?.estimates.range(block_target..).next()?;
+        Some(*fee_rate)
+    }
+}
+
+/// Subscribe to the `rawblock` and `hashblock` notifications of Bitcoin Core, started with
+/// `-zmqpubrawblock` or `-zmqpubhashblock`, updating the block window and the estimates at
+/// every new block.
+/// `hashblock` notifications are used only with a [`BlockSource`] to fetch the block, which is
+/// also used to fill the window at the first block
+pub struct ZmqListener<N> {
+    model: FeeModel<N>,
+    targets: Vec<u16>,
+    socket: zmq::Socket,
+    window: BlockWindow,
+    source: Option<Box<dyn BlockSource + Send>>,
+    estimates: EstimatesHandle,
+}
+
+fn zmq_error(e: zmq::Error) -> Error {
+    Error::BlockSource(format!("zmq: {}", e))
+}
+
+impl<N: SizeMarker> ZmqListener<N> {
+    /// connect to `endpoint` like `tcp://127.0.0.1:28332`, estimates are computed for `targets`
+    pub fn new(model: FeeModel<N>, targets: Vec<u16>, endpoint: &str) -> Result<Self, Error> {
+        let socket = zmq::Context::new().socket(zmq::SUB).map_err(zmq_error)?;
+        socket.connect(endpoint).map_err(zmq_error)?;
+        socket.set_subscribe(b"rawblock").map_err(zmq_error)?;
+        socket.set_subscribe(b"hashblock").map_err(zmq_error)?;
+        Ok(ZmqListener {
+            model,
+            targets,
+            socket,
+            window: BlockWindow::new(),
+            source: None,
+            estimates: EstimatesHandle::default(),
+        })
+    }
+
+    pub fn with_block_source(mut self, source: Box<dyn BlockSource + Send>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn handle(&self) -> EstimatesHandle {
+        self.estimates.clone()
+    }
+
+    /// wait for the next notification, returns `true` if the estimates have been updated.
+    /// Invalid notifications are ignored, errors are returned only by the socket or the block
+    /// source
+    pub fn recv(&mut self) -> Result<bool, Error> {
+        let parts = self.socket.recv_multipart(0).map_err(zmq_error)?;
+        let (topic, body) = match &parts[..] {
+            [topic, body, ..] => (topic, body),
+            _ => return Ok(false),
+        };
+        let block = match (&topic[..], &self.source) {
+            (b"rawblock", _) => match deserialize::<Block>(body) {
+                Ok(block) => block,
+                Err(_) => return Ok(false),
+            },
+            (b"hashblock", Some(source)) => match BlockHash::from_slice(body) {
+                // notified in the reversed byte order used for display
+                Ok(hash) => {
+                    let mut bytes = hash.into_inner();
+                    bytes.reverse();
+                    source.block(&BlockHash::from_inner(bytes))?
+                }
+                Err(_) => return Ok(false),
+            },
+            _ => return Ok(false),
+        };
+        self.push_block(block)
+    }
+
+    /// add `block` as the new tip and update the estimates, returns `true` if they changed
+    pub fn push_block(&mut self, block: Block) -> Result<bool, Error> {
+        let tip = block.block_hash();
+        if self.window.tip().map(|b| b.block_hash()) == Some(tip) {
+            // notified by both `rawblock` and `hashblock`
+            return Ok(false);
+        }
+        self.window.push(block);
+        if let (false, Some(source)) = (self.window.is_full(), &self.source) {
+            self.window = BlockWindow::new();
+            for block in source.last_blocks(10)? {
+                self.window.push(block);
+            }
+        }
+        let (fee_rates, last_block_ts) = match self.window.process() {
+            Ok(processed) => processed,
+            Err(_) => return Ok(false),
+        };
+        let estimates = self
+            .targets
+            .iter()
+            .filter_map(|t| {
+                let estimate = self.model.estimate(*t, None, &fee_rates, last_block_ts);
+                estimate.ok().map(|e| (*t, e))
+            })
+            .collect();
+        let tip = self.window.tip().map(|b| b.block_hash()).unwrap_or(tip);
+        *self.estimates.0.write().unwrap() = Some(EstimateCurve { tip, estimates });
+        Ok(true)
+    }
+
+    /// receive notifications until an error occurs
+    pub fn run(mut self) -> Result<(), Error> {
+        loop {
+            self.recv()?;
+        }
+    }
+
+    /// [`ZmqListener::run`] in a new thread
+    pub fn spawn(self) -> JoinHandle<Result<(), Error>>
+    where
+        N: Send + 'static,
+    {
+        std::thread::spawn(move || self.run())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZmqListener;
+    use crate::block_files::tests::chain;
+    use crate::block_source::MemoryBlockSource;
+    use crate::{get_model_high, get_model_low, FeeModel};
+    use bitcoin::consensus::serialize;
+    use bitcoin::hashes::Hash;
+    use std::time::Duration;
+
+    fn publisher() -> (zmq::Socket, String) {
+        let socket = zmq::Context::new().socket(zmq::PUB).unwrap();
+        socket.bind("tcp://127.0.0.1:*").unwrap();
+        let endpoint = socket.get_last_endpoint().unwrap().unwrap();
+        (socket, endpoint)
+    }
+
+    fn model() -> FeeModel<crate::Size64> {
+        FeeModel::new(get_model_low(), get_model_high())
+    }
+
+    #[test]
+    fn test_rawblock() {
+        let mut blocks = chain(10);
+        let tx = blocks[0].txdata[0].clone();
+        blocks[0].txdata.push(tx);
+        let (publisher, endpoint) = publisher();
+        let listener = ZmqListener::new(model(), vec![1, 6, 144], &endpoint).unwrap();
+        let handle = listener.handle();
+        listener.spawn();
+
+        // the first messages are lost until the subscription reaches the publisher, resending
+        // the first block restarts the window
+        for _ in 0..50 {
+            for (i, block) in blocks.iter().enumerate() {
+                let seq = (i as u32).to_le_bytes();
+                publisher
+                    .send_multipart(
+                        vec![b"rawblock".to_vec(), serialize(block), seq.to_vec()],
+                        0,
+                    )
+                    .unwrap();
+            }
+            std::thread::sleep(Duration::from_millis(100));
+            if handle.latest().is_some() {
+                break;
+            }
+        }
+
+        let curve = handle.latest().unwrap();
+        assert_eq!(curve.tip, blocks[9].block_hash());
+        assert_eq!(curve.estimates.len(), 3);
+        assert_eq!(handle.estimate(2), curve.estimates.get(&6).cloned());
+        assert_eq!(handle.estimate(1000), None);
+    }
+
+    #[test]
+    fn test_hashblock() {
+        let mut blocks = chain(11);
+        let tx = blocks[1].txdata[0].clone();
+        blocks[1].txdata.push(tx);
+        let (_publisher, endpoint) = publisher();
+        let source = MemoryBlockSource::new(blocks.clone());
+        let mut listener = ZmqListener::new(model(), vec![1], &endpoint)
+            .unwrap()
+            .with_block_source(Box::new(source));
+        let handle = listener.handle();
+
+        // the window is filled by the source at the first block
+        assert!(listener.push_block(blocks[10].clone()).unwrap());
+        assert_eq!(handle.latest().unwrap().tip, blocks[10].block_hash());
+        assert!(!listener.push_block(blocks[10].clone()).unwrap());
+
+        // hashblock notifications carry the hash in the reversed byte order
+        let mut hash = blocks[9].block_hash().into_inner();
+        hash.reverse();
+        let (publisher, endpoint) = publisher();
+        let listener = ZmqListener::new(model(), vec![1], &endpoint)
+            .unwrap()
+            .with_block_source(Box::new(MemoryBlockSource::new(blocks[..10].to_vec())));
+        let handle = listener.handle();
+        listener.spawn();
+        for _ in 0..50 {
+            publisher
+                .send_multipart(vec![b"hashblock".to_vec(), hash.to_vec(), vec![0u8; 4]], 0)
+                .unwrap();
+            std::thread::sleep(Duration::from_millis(100));
+            if handle.latest().is_some() {
+                break;
+            }
+        }
+        assert_eq!(handle.latest().unwrap().tip, blocks[9].block_hash());
+    }
+}